@@ -0,0 +1,155 @@
+//compares the synchronous codec with the async `Reader`/`Writer` path
+//both are measured on the work done for one full datagram of frames
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raknet::{
+    packets::*,
+    reader::{Endian, Reader},
+    writer::Writer,
+};
+
+//the async path never waits on an in-memory cursor, a single poll completes it
+fn ready<F: Future>(future: F) -> F::Output {
+    let mut context = Context::from_waker(Waker::noop());
+    match pin!(future).poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!(),
+    }
+}
+
+fn frame_set() -> FrameSet<'static> {
+    let datas = (0..12u32)
+        .map(|i| {
+            let mut frame = Frame::new(Reliability::ReliableOrdered, &[0xfe; 100]);
+            frame.message_index = i;
+            frame.order_index = i;
+            frame
+        })
+        .collect();
+    FrameSet {
+        header: 0x84,
+        sequence_number: 1,
+        datas,
+    }
+}
+
+async fn encode_async(set: &FrameSet<'_>) -> Vec<u8> {
+    let mut cursor = Writer::new(vec![]);
+    cursor.write_u8(set.header).await.unwrap();
+    cursor
+        .write_u24(set.sequence_number, Endian::Little)
+        .await
+        .unwrap();
+    for frame in set.datas.iter() {
+        cursor.write_u8(0x3 << 5).await.unwrap();
+        cursor
+            .write_u16((frame.data.len() * 8) as u16, Endian::Big)
+            .await
+            .unwrap();
+        cursor
+            .write_u24(frame.message_index, Endian::Little)
+            .await
+            .unwrap();
+        cursor
+            .write_u24(frame.order_index, Endian::Little)
+            .await
+            .unwrap();
+        cursor.write_u8(0).await.unwrap();
+        cursor.write(&frame.data).await.unwrap();
+    }
+    cursor.get_raw_payload()
+}
+
+async fn decode_async(payload: &[u8]) -> Vec<Vec<u8>> {
+    let mut cursor = Reader::new(payload);
+    cursor.read_u8().await.unwrap();
+    cursor.read_u24(Endian::Little).await.unwrap();
+    let mut datas = vec![];
+    while cursor.pos() < payload.len() as u64 {
+        cursor.read_u8().await.unwrap();
+        let length = cursor.read_u16(Endian::Big).await.unwrap() >> 3;
+        cursor.read_u24(Endian::Little).await.unwrap();
+        cursor.read_u24(Endian::Little).await.unwrap();
+        cursor.next(1);
+        let mut data = vec![0u8; length as usize];
+        cursor.read(&mut data).await.unwrap();
+        datas.push(data);
+    }
+    datas
+}
+
+fn frame_set_codec(c: &mut Criterion) {
+    let set = frame_set();
+    let payload = set.encode();
+    assert_eq!(ready(encode_async(&set)), payload);
+
+    let mut group = c.benchmark_group("frame_set");
+    group.bench_function("encode/async", |b| {
+        b.iter(|| ready(encode_async(black_box(&set))))
+    });
+    group.bench_function("encode/sync", |b| b.iter(|| black_box(&set).encode()));
+    let mut buff = Vec::with_capacity(set.length());
+    group.bench_function("encode_into/sync", |b| {
+        b.iter(|| {
+            buff.clear();
+            black_box(&set).encode_into(&mut buff);
+        })
+    });
+    group.bench_function("decode/async", |b| {
+        b.iter(|| ready(decode_async(black_box(&payload))))
+    });
+    group.bench_function("decode/sync", |b| {
+        b.iter(|| FrameSet::decode(black_box(&payload)).unwrap())
+    });
+    group.finish();
+}
+
+fn pong_codec(c: &mut Criterion) {
+    let pong = ConnectedPong::new(1, 2);
+    let payload = encode(pong.clone());
+
+    let mut group = c.benchmark_group("connected_pong");
+    group.bench_function("encode/async", |b| {
+        b.iter(|| {
+            ready(async {
+                let mut cursor = Writer::new(vec![]);
+                cursor.write_u8(ConnectedPong::ID).await.unwrap();
+                cursor
+                    .write_i64(black_box(pong.client_timestamp), Endian::Big)
+                    .await
+                    .unwrap();
+                cursor
+                    .write_i64(black_box(pong.server_timestamp), Endian::Big)
+                    .await
+                    .unwrap();
+                cursor.get_raw_payload()
+            })
+        })
+    });
+    group.bench_function("encode/sync", |b| {
+        b.iter(|| encode(black_box(pong.clone())))
+    });
+    group.bench_function("decode/async", |b| {
+        b.iter(|| {
+            ready(async {
+                let mut cursor = Reader::new(black_box(&payload[1..]));
+                (
+                    cursor.read_i64(Endian::Big).await.unwrap(),
+                    cursor.read_i64(Endian::Big).await.unwrap(),
+                )
+            })
+        })
+    });
+    group.bench_function("decode/sync", |b| {
+        b.iter(|| decode::<ConnectedPong>(black_box(&payload)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, frame_set_codec, pong_codec);
+criterion_main!(benches);
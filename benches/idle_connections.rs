@@ -0,0 +1,97 @@
+//measures the cpu time spent while many connections sit idle
+//RAKNET_BENCH_CONNECTIONS, RAKNET_BENCH_SECONDS and RAKNET_BENCH_SETUP_SECONDS override the defaults,
+//connections that are not up once the setup time is over are left out of the measurement
+//
+//to compare with another revision, e.g. the 10ms scan that came before the connection drivers:
+//  git worktree add ../baseline <revision>
+//  cp benches/idle_connections.rs ../baseline/benches/
+//  add libc to the dev-dependencies of ../baseline/Cargo.toml along with
+//  [[bench]] name = "idle_connections" harness = false
+//  then run `cargo bench --bench idle_connections` in both trees with the same settings
+use std::{net::SocketAddr, time::Duration};
+
+use tokio::time::{timeout_at, Instant};
+
+use raknet::{Client, Server};
+
+//open connection requests are padded to the mtu, larger batches overflow the socket buffer
+const BATCH: usize = 50;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+//user + system time of the whole process
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let micros = |t: libc::timeval| t.tv_sec as u64 * 1_000_000 + t.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+#[tokio::main]
+async fn main() {
+    let connections = env_or("RAKNET_BENCH_CONNECTIONS", 5000);
+    let seconds = env_or("RAKNET_BENCH_SECONDS", 10);
+    let setup =
+        Instant::now() + Duration::from_secs(env_or("RAKNET_BENCH_SETUP_SECONDS", 120) as u64);
+
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "bench".to_owned());
+    server.listen().await.unwrap();
+    let address: SocketAddr = server.local_addr;
+
+    let mut clients = Vec::with_capacity(connections);
+    'setup: while clients.len() < connections {
+        let batch = BATCH.min(connections - clients.len());
+        let mut handles = Vec::with_capacity(batch);
+        for _ in 0..batch {
+            handles.push(tokio::spawn(async move {
+                //the handshake is not retried, a lost request means starting over
+                loop {
+                    let mut client = Client::new(address, false).await.unwrap();
+                    if client.connect().await.is_ok() {
+                        client.listen().await;
+                        return client;
+                    }
+                }
+            }));
+        }
+        for handle in handles.iter_mut() {
+            match timeout_at(setup, handle).await {
+                Ok(client) => clients.push(client.unwrap()),
+                Err(_) => {
+                    //the attempts still running would retry during the measurement
+                    handles.iter().for_each(|handle| handle.abort());
+                    break 'setup;
+                }
+            }
+        }
+    }
+
+    let mut peers = Vec::with_capacity(clients.len());
+    while peers.len() < clients.len() {
+        match timeout_at(setup + Duration::from_secs(10), server.accept()).await {
+            Ok(peer) => peers.push(peer.unwrap()),
+            Err(_) => break,
+        }
+    }
+
+    //let the handshake traffic settle
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let start = cpu_time();
+    tokio::time::sleep(Duration::from_secs(seconds as u64)).await;
+    let used = cpu_time() - start;
+
+    println!(
+        "{} of {} idle connections for {}s: {:?} cpu ({:.1}% of one core)",
+        peers.len(),
+        connections,
+        seconds,
+        used,
+        used.as_secs_f64() * 100.0 / seconds as f64
+    );
+}
@@ -1,10 +1,7 @@
 use raknet::{Client, RaknetEvent};
-use std::net::ToSocketAddrs;
 
 async fn client() {
-    let mut remote = "127.0.0.1:19132".to_socket_addrs().unwrap();
-    let mut client = Client::new(remote.next().unwrap(), true).await.unwrap();
-    client.connect().await.unwrap();
+    let mut client = Client::connect_host("127.0.0.1:19132").await.unwrap();
     client.listen().await;
     loop {
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
@@ -30,6 +27,7 @@ async fn client() {
                     eprintln!("{} {}", addr, error);
                     disconnected = true;
                 }
+                _ => {}
             }
         }
         if disconnected {
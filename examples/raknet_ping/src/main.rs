@@ -3,18 +3,19 @@ use std::net::{SocketAddr, ToSocketAddrs};
 
 #[tokio::main]
 async fn main() {
-    let pinger = Ping::new().await;
+    let pinger = Ping::new().await.unwrap();
     let remote = "hivebedrock.network:19132"
         .to_socket_addrs()
         .unwrap()
         .collect::<Vec<SocketAddr>>();
 
-    let mut result = String::new();
     for addr in remote {
-        if let Ok(p) = pinger.ping(addr).await {
-            result = p;
+        if let Ok(pong) = pinger.ping(addr).await {
+            println!("{} {:?} {}", pong.guid, pong.rtt, pong.motd);
+            if let Ok(motd) = pong.bedrock_motd() {
+                println!("{} {}/{}", motd.name, motd.players, motd.max_players);
+            }
             break;
         }
     }
-    println!("{:02X?}", result);
 }
@@ -26,6 +26,7 @@ async fn server() {
                 RaknetEvent::Error(addr, error) => {
                     eprintln!("{} {}", addr, error);
                 }
+                _ => {}
             }
         }
     }
@@ -61,6 +62,7 @@ async fn client() {
                     eprintln!("{} {}", addr, error);
                     disconnected = true;
                 }
+                _ => {}
             }
         }
         if disconnected {
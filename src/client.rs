@@ -1,22 +1,59 @@
 use rand::random;
-use std::{io::Result, net::SocketAddr, panic, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::Result,
+    net::SocketAddr,
+    panic,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
 use tokio::{
     net::UdpSocket,
-    sync::{mpsc::Receiver, Mutex},
+    sync::{mpsc, watch, Mutex},
+    task::{JoinHandle, JoinSet},
 };
 
-use crate::rak::{RaknetError, RaknetEvent};
-use crate::{connection::Connection, packets::*};
+use crate::rak::{DisconnectReason, RaknetError, RaknetEvent};
+use crate::{
+    connection::{Connection, RaknetType},
+    driver::{drive, wait_pong, ConnectionHandle, INBOUND_CAPACITY},
+    eventqueue::{event_queue, EventReceiver},
+    handshake::Handshake,
+    packets::*,
+    server::UNCONNECTED_BACKLOG,
+    time, EventQueueConfig, Latency, QueuedMessages, RaknetStatistics, ReconnectPolicy,
+    RemoteClock,
+};
 
 use crate::macros::*;
+use crate::transport::{normalise, outbound, Dial, Transport};
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
-const RAKNET_PROTOCOL_VERSION: u8 = 0xA;
+//head start of a connection attempt before the next candidate is tried alongside it
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
 
-pub struct Client {
-    socket: Arc<UdpSocket>,
-    connection: Arc<Mutex<Option<Connection>>>,
+//where the server's datagrams go, to the handshake or connection under way
+type Route = Arc<std::sync::Mutex<Option<mpsc::Sender<Vec<u8>>>>>;
+
+pub struct Client<T = UdpSocket> {
+    socket: Option<Arc<T>>,
+    session: Arc<std::sync::Mutex<Session>>,
     event: Arc<Mutex<Vec<RaknetEvent>>>,
-    reveiver: Arc<Mutex<Option<Receiver<RaknetEvent>>>>,
+    reveiver: Arc<Mutex<Option<EventReceiver>>>,
+    route: Route,
+    //offline messages waiting for `recv`, read from bind time on
+    unconnected: Mutex<mpsc::Receiver<RaknetEvent>>,
+    dropped_unconnected: Arc<AtomicU64>,
+    event_queue: EventQueueConfig,
+    reconnect: Option<ReconnectPolicy>,
+    tasks: Vec<JoinHandle<()>>,
+    stop: watch::Sender<()>,
+    //set by `disconnect` and `shutdown`, ends a reconnect under way
+    closing: watch::Sender<bool>,
 
     pub guid: u64,
     pub mtu: u16,
@@ -24,187 +61,680 @@ pub struct Client {
     pub local: SocketAddr,
 }
 
-impl Client {
+impl Client<UdpSocket> {
     pub async fn new(remote_address: SocketAddr, online: bool) -> std::io::Result<Self> {
-        let local: SocketAddr = {
-            if online {
-                "0.0.0.0:0".parse().unwrap()
-            } else {
-                "127.0.0.1:0".parse().unwrap()
-            }
+        let remote_address = normalise(remote_address);
+        let local: SocketAddr = match (online, remote_address.is_ipv6()) {
+            (true, false) => "0.0.0.0:0".parse().unwrap(),
+            (false, false) => "127.0.0.1:0".parse().unwrap(),
+            (true, true) => "[::]:0".parse().unwrap(),
+            (false, true) => "[::1]:0".parse().unwrap(),
         };
-        let socket = Arc::new(UdpSocket::bind(local).await?);
-        Ok(Self {
-            socket,
-            remote: remote_address,
-            connection: Arc::new(Mutex::new(None)),
+        Self::bind(local, remote_address).await
+    }
+
+    /// Binds to `local`, e.g. one interface of a multi-homed host or a fixed port.
+    /// `local` holds the address that was actually bound.
+    pub async fn bind(local: SocketAddr, remote_address: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(local).await?;
+        let local = socket.local_addr()?;
+        Ok(Self::build(socket, remote_address, local))
+    }
+
+    /// Resolves `host` ("name:port") and connects to the first of its addresses
+    /// that completes the handshake.
+    ///
+    /// IPv6 and IPv4 candidates are tried alternately, every attempt gets a head
+    /// start of 250ms before the next one is started alongside it (Happy Eyeballs).
+    /// If none connects, `RaknetError::ConnectFailed` holds each candidate's error.
+    pub async fn connect_host(host: &str) -> std::result::Result<Self, RaknetError> {
+        let addresses = match tokio::net::lookup_host(host).await {
+            Ok(addresses) => addresses.collect(),
+            Err(e) => return Err(RaknetError::Other(format!("{}: {}", host, e))),
+        };
+        let mut candidates = interleave(addresses).into_iter();
+        let mut attempts = JoinSet::new();
+        //candidate of each attempt, also for the ones that panicked
+        let mut remotes = HashMap::new();
+        let mut failed = vec![];
+        let mut next = candidates.next();
+        loop {
+            if let Some(remote) = next.take() {
+                let attempt = attempts.spawn(Self::attempt(remote));
+                remotes.insert(attempt.id(), remote);
+            }
+            if attempts.is_empty() {
+                return Err(RaknetError::ConnectFailed(failed));
+            }
+            let more = candidates.len() != 0;
+            tokio::select! {
+                finished = attempts.join_next_with_id() => {
+                    let (remote, e) = match finished {
+                        Some(Ok((_, Ok(client)))) => return Ok(client),
+                        Some(Ok((id, Err(e)))) => (remotes[&id], e),
+                        Some(Err(e)) => (remotes[&e.id()], RaknetError::Other(e.to_string())),
+                        None => continue,
+                    };
+                    failed.push((remote, e));
+                    next = candidates.next();
+                },
+                _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if more => {
+                    next = candidates.next();
+                }
+            }
+        }
+    }
+
+    async fn attempt(remote: SocketAddr) -> std::result::Result<Self, RaknetError> {
+        let mut client = Self::new(remote, true)
+            .await
+            .map_err(|e| RaknetError::Other(format!("{}", e)))?;
+        client.connect().await?;
+        Ok(client)
+    }
+}
+
+//alternates between the address families, starting with the one the resolver put first
+fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let mut seen = HashSet::new();
+    let first_v6 = addresses.first().is_some_and(|address| address.is_ipv6());
+    let (mut preferred, mut other): (VecDeque<_>, VecDeque<_>) = addresses
+        .into_iter()
+        .filter(|address| seen.insert(*address))
+        .partition(|address| address.is_ipv6() == first_v6);
+    let mut ordered = vec![];
+    while !preferred.is_empty() || !other.is_empty() {
+        ordered.extend(preferred.pop_front());
+        ordered.extend(other.pop_front());
+    }
+    ordered
+}
+
+//what the application talks to, the reconnect policy swaps the connection
+#[derive(Default)]
+struct Session {
+    connection: Option<Arc<ConnectionHandle>>,
+    //messages sent while reconnecting, None while the connection is up
+    outage: Option<Vec<Vec<u8>>>,
+}
+
+fn lock(session: &std::sync::Mutex<Session>) -> MutexGuard<'_, Session> {
+    session.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<T: Transport> Client<T> {
+    /// Connects over `transport` instead of a UDP socket of its own.
+    /// Must be called within a tokio runtime, the transport is read from here on.
+    pub fn with_transport(transport: T, remote_address: SocketAddr) -> Result<Self> {
+        let local = transport.local_addr()?;
+        Ok(Self::build(transport, remote_address, local))
+    }
+
+    fn build(transport: T, remote_address: SocketAddr, local: SocketAddr) -> Self {
+        let socket = Arc::new(transport);
+        let remote = normalise(remote_address);
+        let route = Route::default();
+        let (unconnected_sender, unconnected) = mpsc::channel(UNCONNECTED_BACKLOG);
+        let dropped_unconnected = Arc::new(AtomicU64::new(0));
+        let receive = tokio::spawn(receive(
+            socket.clone(),
+            remote,
+            route.clone(),
+            unconnected_sender,
+            dropped_unconnected.clone(),
+        ));
+        Self {
+            socket: Some(socket),
+            remote,
+            session: Arc::new(std::sync::Mutex::new(Session::default())),
             event: Arc::new(Mutex::new(vec![])),
             guid: random::<u64>(),
             mtu: 1492,
             local,
             reveiver: Arc::new(Mutex::new(None)),
-        })
+            route,
+            unconnected: Mutex::new(unconnected),
+            dropped_unconnected,
+            event_queue: EventQueueConfig::default(),
+            reconnect: None,
+            tasks: vec![receive],
+            stop: watch::channel(()).0,
+            closing: watch::channel(false).0,
+        }
+    }
+
+    fn connection(&self) -> Option<Arc<ConnectionHandle>> {
+        lock(&self.session).connection.clone()
     }
 
+    //keeps the connection of `connect` going, offline messages arrive without it
     pub async fn listen(&mut self) {
-        if self.connection.lock().await.as_ref().is_none() {
-            panic!("You must connect before listen")
-        }
-
-        let socket = self.socket.clone();
-        let connection = self.connection.clone();
-        let event = self.event.clone();
-        let remote = self.remote;
-        tokio::spawn(async move {
-            let mut v = [0u8; 1500];
-            loop {
-                let (size, source) = match socket.recv_from(&mut v).await {
-                    Ok(p) => p,
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::ConnectionReset {
-                            event.lock().await.push(RaknetEvent::Error(
-                                remote,
-                                RaknetError::RemoteClosed(remote),
-                            ))
-                        }
-                        continue;
-                    }
-                };
-
-                if source != remote || size == 0 {
-                    continue;
-                }
+        let connection = match self.connection() {
+            Some(connection) => connection,
+            None => return,
+        };
 
-                let buff = &v[..size];
-                connection.lock().await.as_mut().unwrap().handle(buff).await;
-            }
-        });
+        let socket = match self.socket.clone() {
+            Some(socket) => socket,
+            None => return,
+        };
+        let link = Link {
+            socket,
+            remote: self.remote,
+            local: self.local,
+            guid: self.guid,
+            mtu: self.mtu,
+            event: self.event.clone(),
+            receiver: self.reveiver.clone(),
+            route: self.route.clone(),
+            session: self.session.clone(),
+            event_queue: self.event_queue,
+            reconnect: self.reconnect,
+            stop: self.stop.subscribe(),
+            closing: self.closing.subscribe(),
+        };
+        self.tasks.push(tokio::spawn(link.run(connection)));
+    }
 
-        let connections = self.connection.clone();
+    /// Disconnects from the server, waits until pending reliable data is acknowledged
+    /// or a deadline passes, then stops the background tasks and releases the socket.
+    pub async fn shutdown(&mut self) {
+        self.disconnect().await;
 
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-                connections.lock().await.as_mut().unwrap().update().await;
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !self.tasks.is_empty() && Instant::now() < deadline {
+            match self.connection() {
+                Some(conn) if !conn.is_finished() => {}
+                _ => break,
             }
-        });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        for task in self.tasks.drain(..) {
+            task.abort();
+            let _ = task.await;
+        }
+        let _ = self.stop.send(());
+        self.socket = None;
     }
 
     pub async fn connect(&mut self) -> std::result::Result<(), RaknetError> {
-        let request1 = OpenConnectionRequest1::new(RAKNET_PROTOCOL_VERSION, self.mtu);
-        let payload = match encode(request1).await {
-            Ok(p) => p,
-            Err(_) => {
-                return Err(RaknetError::Other(
-                    "Failed to encode OpenconnectionRequest1".to_owned(),
+        let socket = match self.socket.clone() {
+            Some(socket) => socket,
+            None => return Err(RaknetError::Other("Client has been shut down".to_owned())),
+        };
+        let server_guid = dial(
+            &socket,
+            &self.route,
+            self.remote,
+            self.local,
+            self.guid,
+            self.mtu,
+            Duration::from_secs(10),
+        )
+        .await?;
+
+        let (connection, receiver) = open(
+            self.remote,
+            self.guid,
+            server_guid,
+            self.mtu,
+            self.event_queue,
+            self.reconnect.is_some(),
+            false,
+        );
+        self.closing.send_replace(false);
+        *self.reveiver.lock().await = Some(receiver);
+        lock(&self.session).connection = Some(connection);
+        Ok(())
+    }
+
+    //must be called before connect
+    pub fn set_event_queue(&mut self, config: EventQueueConfig) {
+        self.event_queue = config;
+    }
+
+    /// Reconnects on its own after the connection timed out instead of reporting
+    /// `Disconnected`, see `ReconnectPolicy`. Must be called before connect.
+    pub fn set_reconnect(&mut self, policy: ReconnectPolicy) {
+        self.reconnect = Some(policy);
+    }
+
+    //None before connect
+    pub async fn stats(&self) -> Option<RaknetStatistics> {
+        Some(self.connection()?.stats())
+    }
+
+    pub async fn latency(&self) -> Option<Latency> {
+        self.connection()?.latency()
+    }
+
+    //offset to the server clock, converts timestamps between both sides
+    pub async fn remote_clock(&self) -> Option<RemoteClock> {
+        self.connection()?.remote_clock()
+    }
+
+    //sends a connected ping and waits for the round trip time in milliseconds
+    pub async fn ping(&self) -> Result<u128> {
+        let pong = match self.connection() {
+            Some(conn) => conn.ping(),
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Not connected",
                 ))
             }
         };
-        match self.socket.send_to(&payload, self.remote).await {
-            Ok(p) => p,
-            Err(e) => return Err(RaknetError::Other(format!("{}", e))),
-        };
-        let timeout =
-            tokio::time::timeout(std::time::Duration::from_secs(10), self.connect_to_server());
+        wait_pong(pong).await
+    }
 
-        match timeout.await {
-            Ok(p) => p?,
-            Err(_) => return Err(RaknetError::RemoteClosed(self.remote)),
-        };
+    //offline messages dropped because `recv` was not called in time
+    pub fn dropped_unconnected(&self) -> u64 {
+        self.dropped_unconnected.load(Ordering::Relaxed)
+    }
 
-        Ok(())
+    pub async fn dropped_events(&self) -> u64 {
+        match self.reveiver.lock().await.as_ref() {
+            Some(receiver) => receiver.dropped(),
+            None => 0,
+        }
     }
 
-    async fn connect_to_server(&mut self) -> std::result::Result<(), RaknetError> {
-        let socket = self.socket.clone();
-        let connection2 = self.connection.clone();
-        let guid = self.guid;
-        let mtu = self.mtu;
-        let remote = self.remote;
-        let receiver2 = self.reveiver.clone();
-        let mut v = [0u8; 1500];
-        loop {
-            let (size, source) = match socket.recv_from(&mut v).await {
-                Ok(p) => p,
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::ConnectionReset {
-                        return Err(RaknetError::RemoteClosed(remote));
-                    }
-                    continue;
-                }
-            };
+    //also ends a reconnect under way, which reports `Disconnected` with `Closed`
+    pub async fn disconnect(&mut self) {
+        self.closing.send_replace(true);
+        if let Some(conn) = self.connection() {
+            conn.close(DisconnectReason::Closed);
+        }
+    }
 
-            if size == 0 {
-                continue;
+    //while reconnecting the message is queued, see `ReconnectPolicy::queued`
+    pub async fn send(&mut self, buff: &[u8]) -> Result<()> {
+        let mut session = lock(&self.session);
+        if let Some(queued) = session.outage.as_mut() {
+            if let Some(ReconnectPolicy {
+                queued: QueuedMessages::Replay,
+                ..
+            }) = self.reconnect
+            {
+                queued.push(buff.to_vec());
             }
+            return Ok(());
+        }
+        if let Some(conn) = session.connection.as_ref() {
+            conn.send(buff);
+        }
+        Ok(())
+    }
 
-            let buff = &v[..size];
+    /// Sends `buff` to `address` without a connection, it arrives as `RaknetEvent::Unconnected`.
+    /// At most `MAX_OUT_OF_BAND` bytes. Replies are reported by `recv`, with or without a connection.
+    pub async fn send_unconnected(&self, address: SocketAddr, buff: &[u8]) -> Result<()> {
+        let socket = match self.socket.as_ref() {
+            Some(socket) => socket,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Client has been shut down",
+                ))
+            }
+        };
+        let payload = encode_out_of_band(self.guid, buff)?;
+        let target = outbound(normalise(address), &self.local);
+        socket.send_to(&payload, target).await?;
+        Ok(())
+    }
 
-            if !source.eq(&remote) {
-                continue;
+    pub async fn recv(&self) -> Result<Vec<RaknetEvent>> {
+        let mut events: Vec<RaknetEvent> = vec![];
+        while let Ok(event) = self.unconnected.lock().await.try_recv() {
+            events.push(event);
+        }
+        events.append(&mut *self.event.lock().await);
+        if let Some(receiver) = self.reveiver.lock().await.as_mut() {
+            while let Some(event) = receiver.try_recv() {
+                events.push(event);
             }
+        }
+        Ok(events)
+    }
+}
 
-            match buff[0] {
-                OpenConnectionReply1::ID => {
-                    let reply1 = unwrap_or_continue!(decode::<OpenConnectionReply1>(buff).await);
-                    let request2 = OpenConnectionRequest2::new(source, reply1.mtu_size, guid);
-                    let payload = unwrap_or_continue!(encode(request2).await);
-                    unwrap_or_continue!(socket.send_to(&payload, source).await);
-                }
-                OpenConnectionReply2::ID => {
-                    let reply2 = unwrap_or_continue!(decode::<OpenConnectionReply2>(buff).await);
-                    let (s, r) = tokio::sync::mpsc::channel::<RaknetEvent>(10);
-                    *receiver2.lock().await = Some(r);
-                    let connection = Connection::new(
-                        source,
-                        socket.clone(),
-                        guid,
-                        reply2.guid,
-                        mtu,
-                        s,
-                        crate::connection::RaknetType::Client,
-                    );
-                    *connection2.lock().await = Some(connection);
-                    connection2.lock().await.as_mut().unwrap().connect().await;
-                    return Ok(());
+impl<T> Drop for Client<T> {
+    fn drop(&mut self) {
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+    }
+}
+
+//offline part of the handshake, yields the guid of the server
+pub(crate) async fn handshake<T: Transport>(
+    socket: &T,
+    remote: SocketAddr,
+    local: SocketAddr,
+    guid: u64,
+    mtu: u16,
+) -> std::result::Result<u64, RaknetError> {
+    let mut handshake = Handshake::new(remote, guid, mtu);
+    let target = outbound(remote, &local);
+    let mut v = [0u8; 1500];
+    loop {
+        while let Some(request) = handshake.poll_transmit() {
+            if let Err(e) = socket.send_to(&request, target).await {
+                return Err(RaknetError::Other(format!("{}", e)));
+            }
+        }
+        if let Some(outcome) = handshake.poll_outcome() {
+            return outcome;
+        }
+        let (size, source) = match socket.recv_from(&mut v).await {
+            Ok(p) => p,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::ConnectionReset {
+                    return Err(RaknetError::RemoteClosed(remote));
                 }
-                IncompatibleProtocolVersion::ID => {
-                    let version =
-                        unwrap_or_continue!(decode::<IncompatibleProtocolVersion>(buff).await);
-                    return Err(RaknetError::IncompatibleProtocolVersion(
-                        version.server_protocol,
-                        RAKNET_PROTOCOL_VERSION,
-                    ));
+                return Err(RaknetError::Other(format!("{}", e)));
+            }
+        };
+        if normalise(source) == remote {
+            handshake.handle(&v[..size]);
+        }
+    }
+}
+
+//the offline handshake over the client's socket, `receive` routes the server's replies to it
+async fn dial<T: Transport>(
+    socket: &Arc<T>,
+    route: &Route,
+    remote: SocketAddr,
+    local: SocketAddr,
+    guid: u64,
+    mtu: u16,
+    timeout: Duration,
+) -> std::result::Result<u64, RaknetError> {
+    let (replies, receiver) = mpsc::channel(INBOUND_CAPACITY);
+    *routed(route) = Some(replies);
+    let dial = Dial::new(socket.clone(), remote, receiver);
+    let server_guid =
+        tokio::time::timeout(timeout, handshake(&dial, remote, local, guid, mtu)).await;
+    *routed(route) = None;
+    server_guid.unwrap_or(Err(RaknetError::TimedOut(remote)))
+}
+
+fn routed(route: &Route) -> MutexGuard<'_, Option<mpsc::Sender<Vec<u8>>>> {
+    route.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+//reads the socket from bind time on, offline messages are reported on their own,
+//everything else the server sends goes where `route` points
+async fn receive<T: Transport>(
+    socket: Arc<T>,
+    remote: SocketAddr,
+    route: Route,
+    unconnected: mpsc::Sender<RaknetEvent>,
+    dropped: Arc<AtomicU64>,
+) {
+    let report = |event| {
+        if unconnected.try_send(event).is_err() {
+            dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+    let mut v = [0u8; 1500];
+    loop {
+        let (size, source) = match socket.recv_from(&mut v).await {
+            Ok(p) => p,
+            Err(e) => {
+                //the server's port is closed, other errors end the socket
+                if e.kind() != std::io::ErrorKind::ConnectionReset {
+                    return;
                 }
-                AlreadyConnected::ID => {
-                    let _alredy_connected =
-                        unwrap_or_continue!(decode::<AlreadyConnected>(buff).await);
-                    return Err(RaknetError::AlreadyConnected(remote));
+                report(RaknetEvent::Error(
+                    remote,
+                    RaknetError::RemoteClosed(remote),
+                ));
+                continue;
+            }
+        };
+
+        if size == 0 {
+            continue;
+        }
+        let source = normalise(source);
+        match v[0] {
+            OutOfBand::ID => {
+                let message = unwrap_or_continue!(decode::<OutOfBand>(&v[..size]));
+                report(RaknetEvent::Unconnected(source, message.data));
+            }
+            AdvertiseSystem::ID => {
+                let advertisement = unwrap_or_continue!(decode::<AdvertiseSystem>(&v[..size]));
+                report(RaknetEvent::Advertisement(
+                    source,
+                    advertisement.guid,
+                    advertisement.data,
+                ));
+            }
+            _ if source == remote => {
+                if let Some(route) = routed(&route).as_ref() {
+                    let _ = route.try_send(v[..size].to_vec());
                 }
-                _ => {}
             }
+            _ => {}
         }
     }
+}
 
-    pub async fn disconnect(&mut self) {
-        if let Some(conn) = self.connection.lock().await.as_mut() {
-            conn.disconnect();
+//starts the connected part of the handshake
+fn open(
+    remote: SocketAddr,
+    guid: u64,
+    server_guid: u64,
+    mtu: u16,
+    config: EventQueueConfig,
+    resumable: bool,
+    resumed: bool,
+) -> (Arc<ConnectionHandle>, EventReceiver) {
+    let (s, r) = event_queue(config);
+    let mut connection =
+        Connection::new(remote, guid, server_guid, mtu, RaknetType::Client, time());
+    connection.connect(time());
+    let mut handle = ConnectionHandle::new(connection, s);
+    if resumable {
+        handle = handle.resumable(resumed);
+    }
+    (Arc::new(handle), r)
+}
+
+//everything the background task needs to keep the client connected
+struct Link<T> {
+    socket: Arc<T>,
+    remote: SocketAddr,
+    local: SocketAddr,
+    guid: u64,
+    mtu: u16,
+    event: Arc<Mutex<Vec<RaknetEvent>>>,
+    receiver: Arc<Mutex<Option<EventReceiver>>>,
+    route: Route,
+    session: Arc<std::sync::Mutex<Session>>,
+    event_queue: EventQueueConfig,
+    reconnect: Option<ReconnectPolicy>,
+    stop: watch::Receiver<()>,
+    closing: watch::Receiver<bool>,
+}
+
+impl<T: Transport> Link<T> {
+    async fn run(self, mut connection: Arc<ConnectionHandle>) {
+        let server = connection.opponent_guid();
+        let mut attempt = 0;
+        loop {
+            tokio::join!(self.serve(connection.clone()), self.adopt(&connection));
+            let policy = match self.reconnect {
+                Some(policy) if connection.lost() => policy,
+                _ => return,
+            };
+            if connection.connected() {
+                //a new outage
+                attempt = 0;
+                self.drain().await;
+                lock(&self.session).outage = Some(vec![]);
+            }
+            connection = match self.redial(&policy, server, &mut attempt).await {
+                Some(connection) => connection,
+                None => return,
+            };
         }
     }
-    pub async fn send(&mut self, buff: &[u8]) -> Result<()> {
-        if let Some(conn) = self.connection.lock().await.as_mut() {
-            conn.send_to(buff);
+
+    //drives the connection with what the server sends until the connection is finished
+    async fn serve(&self, connection: Arc<ConnectionHandle>) {
+        let (inbound, inbound_receiver) = mpsc::channel(INBOUND_CAPACITY);
+        *routed(&self.route) = Some(inbound);
+        drive(
+            connection,
+            self.socket.clone(),
+            inbound_receiver,
+            self.stop.clone(),
+        )
+        .await;
+    }
+
+    //puts a reconnected connection in place once its handshake is done,
+    //messages queued during the outage go out first
+    async fn adopt(&self, connection: &Arc<ConnectionHandle>) {
+        if !connection.resumed() {
+            return;
         }
-        Ok(())
+        let connected = tokio::select! {
+            connected = connection.wait_connected() => connected,
+            //the failed handshake leaves it to `redial` to report the close
+            _ = self.closed() => false,
+        };
+        if !connected {
+            connection.close(DisconnectReason::Closed);
+            return;
+        }
+        let mut session = lock(&self.session);
+        //`disconnect` closed the connection of the outage while this one came up
+        if *self.closing.borrow() {
+            drop(session);
+            connection.close(DisconnectReason::Closed);
+            return;
+        }
+        for buff in session.outage.take().unwrap_or_default() {
+            connection.send(&buff);
+        }
+        session.connection = Some(connection.clone());
     }
-    pub async fn recv(&self) -> Result<Vec<RaknetEvent>> {
-        let mut events: Vec<RaknetEvent> = self.event.lock().await.clone();
-        self.event.lock().await.clear();
-        if self.connection.lock().await.as_mut().is_some() {
-            while let Ok(event) = (*self.reveiver.lock().await).as_mut().unwrap().try_recv() {
+
+    //offline handshakes until a server answers, None once the policy gives up
+    async fn redial(
+        &self,
+        policy: &ReconnectPolicy,
+        server: u64,
+        attempt: &mut u32,
+    ) -> Option<Arc<ConnectionHandle>> {
+        loop {
+            if *self.closing.borrow() {
+                self.give_up(server, DisconnectReason::Closed).await;
+                return None;
+            }
+            if *attempt >= policy.max_attempts {
+                self.give_up(server, DisconnectReason::Timeout).await;
+                return None;
+            }
+            *attempt += 1;
+            self.event
+                .lock()
+                .await
+                .push(RaknetEvent::Reconnecting(self.remote, *attempt));
+            let dialed = async {
+                tokio::time::sleep(policy.backoff(*attempt)).await;
+                dial(
+                    &self.socket,
+                    &self.route,
+                    self.remote,
+                    self.local,
+                    self.guid,
+                    self.mtu,
+                    policy.attempt_timeout,
+                )
+                .await
+            };
+            let server_guid = tokio::select! {
+                dialed = dialed => match dialed {
+                    Ok(server_guid) => server_guid,
+                    Err(_) => continue,
+                },
+                _ = self.closed() => {
+                    self.give_up(server, DisconnectReason::Closed).await;
+                    return None;
+                }
+            };
+            if policy.same_server && server_guid != server {
+                self.give_up(server, DisconnectReason::ServerChanged).await;
+                return None;
+            }
+            let (connection, receiver) = open(
+                self.remote,
+                self.guid,
+                server_guid,
+                self.mtu,
+                self.event_queue,
+                true,
+                true,
+            );
+            *self.receiver.lock().await = Some(receiver);
+            return Some(connection);
+        }
+    }
+
+    //resolves once the application disconnected
+    async fn closed(&self) {
+        let _ = self.closing.clone().wait_for(|closing| *closing).await;
+    }
+
+    //events of the lost connection come before the ones about reconnecting
+    async fn drain(&self) {
+        let mut events = self.event.lock().await;
+        if let Some(receiver) = self.receiver.lock().await.as_mut() {
+            while let Some(event) = receiver.try_recv() {
                 events.push(event);
             }
         }
-        Ok(events)
+    }
+
+    async fn give_up(&self, server: u64, reason: DisconnectReason) {
+        lock(&self.session).outage = None;
+        self.event
+            .lock()
+            .await
+            .push(RaknetEvent::Disconnected(self.remote, server, reason));
+    }
+}
+
+#[cfg(test)]
+mod client_test {
+    use super::interleave;
+
+    #[test]
+    fn interleave_families() {
+        let addresses = [
+            "[::1]:1",
+            "[::2]:1",
+            "127.0.0.1:1",
+            "[::1]:1",
+            "127.0.0.2:1",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+        let ordered: Vec<String> = interleave(addresses)
+            .iter()
+            .map(|address| address.to_string())
+            .collect();
+        assert_eq!(
+            ordered,
+            ["[::1]:1", "127.0.0.1:1", "[::2]:1", "127.0.0.2:1"]
+        );
     }
 }
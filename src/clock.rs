@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+//like NTP, only the most recent samples are kept and the one with
+//the lowest round trip time wins, since it had the least room for asymmetry
+const CLOCK_SAMPLES: usize = 8;
+
+//estimate of the remote peer's clock, timestamps are milliseconds since the unix epoch
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteClock {
+    //remote time - local time
+    pub offset: i64,
+    //round trip time of the sample the offset was taken from
+    pub rtt: u128,
+}
+
+impl RemoteClock {
+    pub fn to_remote(&self, local: i64) -> i64 {
+        local + self.offset
+    }
+
+    pub fn to_local(&self, remote: i64) -> i64 {
+        remote - self.offset
+    }
+
+    pub fn remote_now(&self) -> i64 {
+        self.to_remote(crate::time() as i64)
+    }
+}
+
+pub(crate) struct ClockFilter {
+    samples: VecDeque<RemoteClock>,
+}
+
+impl ClockFilter {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(CLOCK_SAMPLES),
+        }
+    }
+
+    //sent: local time of the request, remote: remote time of the reply, received: local time of the reply
+    pub fn add(&mut self, sent: i64, remote: i64, received: i64) {
+        if received < sent {
+            return;
+        }
+        let sample = RemoteClock {
+            offset: remote - (sent + (received - sent) / 2),
+            rtt: (received - sent) as u128,
+        };
+        if self.samples.len() == CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn get(&self) -> Option<RemoteClock> {
+        self.samples.iter().min_by_key(|sample| sample.rtt).copied()
+    }
+}
+
+#[test]
+fn clock_filter() {
+    let mut filter = ClockFilter::new();
+    assert!(filter.get().is_none());
+    //remote clock is 1000ms ahead, the second sample was delayed on the way back
+    filter.add(0, 1010, 20);
+    filter.add(100, 1110, 300);
+    let clock = filter.get().unwrap();
+    assert_eq!(clock.offset, 1000);
+    assert_eq!(clock.rtt, 20);
+    assert_eq!(clock.to_remote(5), 1005);
+    assert_eq!(clock.to_local(1005), 5);
+
+    for i in 0..CLOCK_SAMPLES as i64 {
+        filter.add(i * 100, i * 100 + 550, i * 100 + 100);
+    }
+    assert_eq!(filter.get().unwrap().offset, 500);
+}
@@ -0,0 +1,279 @@
+use bytes::{Buf, BufMut};
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    str,
+};
+
+use crate::packets::MAGIC;
+
+//an IPv6 address is a copied `sockaddr_in6`, its family field is the sender's AF_INET6
+#[cfg(windows)]
+pub(crate) const AF_INET6: u16 = 23;
+#[cfg(target_vendor = "apple")]
+pub(crate) const AF_INET6: u16 = 30;
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+pub(crate) const AF_INET6: u16 = 28;
+#[cfg(any(target_os = "netbsd", target_os = "openbsd"))]
+pub(crate) const AF_INET6: u16 = 24;
+#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+pub(crate) const AF_INET6: u16 = 26;
+#[cfg(not(any(
+    windows,
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris",
+    target_os = "illumos"
+)))]
+pub(crate) const AF_INET6: u16 = 10;
+
+//linux, windows, the BSDs, solaris and macos
+pub(crate) fn is_af_inet6(family: u16) -> bool {
+    matches!(family, 10 | 23 | 24 | 26 | 28 | 30)
+}
+
+//reads straight out of the received datagram, `Buf` getters panic on short input so
+//every read checks the remaining length first
+#[derive(Clone)]
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn need(&self, n: usize) -> Result<()> {
+        if self.buf.len() < n {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("needs {} bytes, {} left", n, self.buf.len()),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<()> {
+        self.need(n)?;
+        self.buf.advance(n);
+        Ok(())
+    }
+
+    //borrows from the datagram, nothing is copied
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        self.need(n)?;
+        let (bytes, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.buf)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        self.need(1)?;
+        Ok(self.buf.get_u8())
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        self.need(2)?;
+        Ok(self.buf.get_u16())
+    }
+
+    pub fn u16_le(&mut self) -> Result<u16> {
+        self.need(2)?;
+        Ok(self.buf.get_u16_le())
+    }
+
+    pub fn u24_le(&mut self) -> Result<u32> {
+        self.need(3)?;
+        Ok(self.buf.get_uint_le(3) as u32)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        self.need(4)?;
+        Ok(self.buf.get_u32())
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32> {
+        self.need(4)?;
+        Ok(self.buf.get_u32_le())
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        self.need(8)?;
+        Ok(self.buf.get_u64())
+    }
+
+    pub fn i64(&mut self) -> Result<i64> {
+        self.need(8)?;
+        Ok(self.buf.get_i64())
+    }
+
+    pub fn string(&mut self) -> Result<&'a str> {
+        let size = self.u16()?;
+        let bytes = self.bytes(size.into())?;
+        str::from_utf8(bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn magic(&mut self) -> Result<bool> {
+        Ok(self.bytes(MAGIC.len())? == MAGIC)
+    }
+
+    pub fn address(&mut self) -> Result<SocketAddr> {
+        match self.u8()? {
+            4 => {
+                let ip = self.bytes(4)?;
+                let ip = Ipv4Addr::new(0xff - ip[0], 0xff - ip[1], 0xff - ip[2], 0xff - ip[3]);
+                let port = self.u16()?;
+                Ok(SocketAddr::new(IpAddr::V4(ip), port))
+            }
+            6 => {
+                let family = self.u16_le()?;
+                if !is_af_inet6(family) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("unknown address family {}", family),
+                    ));
+                }
+                let port = self.u16()?;
+                let flowinfo = self.u32()?;
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(self.bytes(16)?);
+                let scope_id = self.u32_le()?;
+                Ok(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(ip),
+                    port,
+                    flowinfo,
+                    scope_id,
+                )))
+            }
+            version => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown address version {}", version),
+            )),
+        }
+    }
+}
+
+//raknet specific fields on top of `BufMut`
+pub trait BufMutExt: BufMut {
+    fn put_u24_le(&mut self, v: u32) {
+        self.put_uint_le(v as u64, 3);
+    }
+
+    fn put_string(&mut self, body: &str) {
+        self.put_u16(body.len() as u16);
+        self.put_slice(body.as_bytes());
+    }
+
+    fn put_magic(&mut self) {
+        self.put_slice(&MAGIC);
+    }
+
+    fn put_address(&mut self, address: SocketAddr) {
+        match address.ip() {
+            IpAddr::V4(ip) => {
+                self.put_u8(0x4);
+                for octet in ip.octets().iter() {
+                    self.put_u8(0xff - octet);
+                }
+                self.put_u16(address.port());
+            }
+            IpAddr::V6(ip) => {
+                let (flowinfo, scope_id) = match address {
+                    SocketAddr::V6(address) => (address.flowinfo(), address.scope_id()),
+                    SocketAddr::V4(_) => (0, 0),
+                };
+                self.put_u8(0x6);
+                self.put_u16_le(AF_INET6);
+                self.put_u16(address.port());
+                self.put_u32(flowinfo);
+                self.put_slice(&ip.octets());
+                self.put_u32_le(scope_id);
+            }
+        }
+    }
+}
+
+impl<B: BufMut + ?Sized> BufMutExt for B {}
+
+#[cfg(test)]
+mod codec_test {
+    use super::{BufMutExt, Decoder};
+    use bytes::BufMut;
+
+    #[cfg(unix)]
+    #[test]
+    fn af_inet6() {
+        assert_eq!(super::AF_INET6, libc::AF_INET6 as u16);
+        assert!(super::is_af_inet6(super::AF_INET6));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = vec![];
+        buf.put_u8(1);
+        buf.put_u24_le(0x123456);
+        buf.put_string("raknet");
+        buf.put_magic();
+        buf.put_address("127.0.0.1:19132".parse().unwrap());
+        buf.put_i64(-1);
+
+        let mut decoder = Decoder::new(&buf);
+        assert_eq!(decoder.u8().unwrap(), 1);
+        assert_eq!(decoder.u24_le().unwrap(), 0x123456);
+        assert_eq!(decoder.string().unwrap(), "raknet");
+        assert!(decoder.magic().unwrap());
+        assert_eq!(
+            decoder.address().unwrap(),
+            "127.0.0.1:19132".parse().unwrap()
+        );
+        assert_eq!(decoder.i64().unwrap(), -1);
+        assert_eq!(decoder.remaining(), 0);
+        assert!(decoder.u8().is_err());
+    }
+
+    #[test]
+    fn ipv6_address() {
+        let address = "[2001:db8::1]:19132".parse().unwrap();
+        let mut buf = vec![];
+        buf.put_address(address);
+        assert_eq!(buf.len(), 29);
+        assert_eq!(Decoder::new(&buf).address().unwrap(), address);
+
+        //written on windows and on linux
+        for family in [23u8, 10] {
+            let mut buf = vec![0x6, family, 0x00, 0x4a, 0xbc, 0, 0, 0, 0];
+            buf.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+            buf.extend_from_slice(&[0, 0, 0, 0]);
+            assert_eq!(
+                Decoder::new(&buf).address().unwrap(),
+                "[::1]:19132".parse().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn bad_address() {
+        assert!(Decoder::new(&[0x5, 0, 0, 0, 0, 0, 0]).address().is_err());
+        let mut buf = vec![0x6, 0x02, 0x00];
+        buf.resize(29, 0);
+        assert!(Decoder::new(&buf).address().is_err());
+    }
+
+    #[test]
+    fn short_string() {
+        let mut decoder = Decoder::new(&[0x00, 0x05, b'a']);
+        assert!(decoder.string().is_err());
+    }
+}
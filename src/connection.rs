@@ -1,28 +1,40 @@
 use crate::{
+    clock::ClockFilter,
     macros::*,
     packet::{ACKQueue, RaknetPacket},
     packetqueue::PacketQueue,
     packets::*,
     receivedqueue::ReceivedQueue,
-    time, DisconnectReason, RaknetEvent,
+    DisconnectReason, Latency, RaknetEvent, RaknetStatistics, RemoteClock,
 };
-use std::{collections::VecDeque, convert::TryInto, net::SocketAddr, sync::Arc};
-use tokio::{net::UdpSocket, sync::mpsc::Sender};
+use std::{collections::VecDeque, convert::TryInto, net::SocketAddr};
+
 const DATAGRAM_FLAG: u8 = 0x80;
 
 const ACK_FLAG: u8 = 0x40;
 
 const NACK_FLAG: u8 = 0x20;
 
+//how long a closed connection keeps resending unacknowledged data
+const CLOSE_TIMEOUT: u128 = 1000;
+
+const TIMEOUT: u128 = 10000;
+
+const PING_INTERVAL: u128 = 5000;
+
 pub enum RaknetType {
     Client,
     Server,
 }
 
+/// The reliability layer of one connection, without any IO or clock.
+///
+/// Feed it received datagrams with `handle`, messages with `send_to` and the
+/// current time in milliseconds with `update`, then take what it produced with
+/// `poll_transmit`, `poll_event` and `poll_pong`. `next_deadline` tells when
+/// `update` has to run again.
 pub struct Connection {
     pub address: SocketAddr,
-    socket: Arc<UdpSocket>,
-    pub event_sender: Sender<RaknetEvent>,
     pub guid: u64,
     pub opponent_guid: u64,
     pub mtu: u16,
@@ -34,178 +46,281 @@ pub struct Connection {
     split_id: u16,
     received: ReceivedQueue,
     last_ping: u128,
+    latency: Latency,
+    clock: ClockFilter,
+    connected: bool,
     dissconnected: bool,
-    recovery_queue: VecDeque<RaknetEvent>,
+    closed_at: u128,
     rak_type: RaknetType,
+    stats: RaknetStatistics,
+    now: u128,
+    //datagrams to send back to back in one reused buffer, and their lengths
+    transmit: Vec<u8>,
+    transmits: VecDeque<usize>,
+    transmitted: usize,
+    events: VecDeque<RaknetEvent>,
+    ping_id: u64,
+    //every ping gets its own timestamp, the pong echoes it back
+    last_ping_stamp: i64,
+    //ids, timestamps and send times of the pings sent with `ping`
+    pending_pings: VecDeque<(u64, i64, i64)>,
+    pongs: VecDeque<(u64, u128)>,
 }
 
 impl Connection {
     pub fn new(
         address: SocketAddr,
-        socket: Arc<UdpSocket>,
         guid: u64,
         opponent_guid: u64,
         mtu: u16,
-        sender: Sender<RaknetEvent>,
         rak_type: RaknetType,
+        now: u128,
     ) -> Self {
-        let time = time();
         Self {
             address,
-            socket,
-            event_sender: sender,
             guid,
             opponent_guid,
             mtu,
-            last_receive: time,
+            last_receive: now,
             ack_queue: ACKQueue::new(),
-            packet_queue: PacketQueue::new(mtu, time),
+            packet_queue: PacketQueue::new(mtu, now),
             message_index: 0,
             order_index: 0,
             split_id: 0,
             received: ReceivedQueue::new(),
-            last_ping: time,
+            last_ping: now,
+            latency: Latency::default(),
+            clock: ClockFilter::new(),
+            connected: false,
             dissconnected: false,
-            recovery_queue: VecDeque::new(),
+            closed_at: 0,
             rak_type,
+            stats: RaknetStatistics::default(),
+            now,
+            transmit: vec![],
+            transmits: VecDeque::new(),
+            transmitted: 0,
+            events: VecDeque::new(),
+            ping_id: 0,
+            last_ping_stamp: 0,
+            pending_pings: VecDeque::new(),
+            pongs: VecDeque::new(),
         }
     }
-    pub async fn update(&mut self) {
-        self.flush_queue().await;
-        self.flush_ack().await;
-        self.recovery();
-        let time = time();
-        if (time - self.last_receive) > 10000 {
-            self.disconnect();
-            self.disconnected(DisconnectReason::Timeout).await;
+    //runs the timers and moves due frame sets and acknowledgements to the outbound queue
+    pub fn update(&mut self, now: u128) {
+        self.now = self.now.max(now);
+        self.flush_queue();
+        self.flush_ack();
+        let time = self.now;
+        if (time - self.last_receive) > TIMEOUT && !self.dissconnected {
+            if self.connected {
+                self.disconnected(DisconnectReason::Timeout);
+            } else {
+                self.disconnected(DisconnectReason::HandshakeFailed);
+            }
+            //nobody is left to acknowledge anything
+            self.closed_at = 0;
         }
-        if (time - self.last_ping) > 5 * 1000 {
-            // 1/s
+        if (time - self.last_ping) > PING_INTERVAL && !self.dissconnected {
             self.last_ping = time;
-            self.send_ping().await;
+            self.send_ping();
+            self.flush_queue();
         }
     }
-    fn recovery(&mut self) {
-        if !self.recovery_queue.is_empty() {
-            while let Some(event) = self.recovery_queue.pop_front() {
-                if self.event_sender.try_send(event).is_err() {
-                    break;
-                }
-            }
-        }
-    }
-    pub async fn connect(&mut self) {
-        let request = ConnectionRequest::new(self.guid, time() as i64, false);
-        let buff = unwrap_or_dbg!(encode(request).await);
+    pub fn connect(&mut self, now: u128) {
+        self.now = self.now.max(now);
+        let request = ConnectionRequest::new(self.guid, self.time_stamp(), false);
+        let buff = encode(request);
         let frame = Frame::new(Reliability::Reliable, &buff);
         self.send(frame);
     }
-    pub async fn handle(&mut self, buff: &[u8]) {
-        let header = buff[0];
+    pub fn handle(&mut self, buff: &[u8], now: u128) {
+        let header = match buff.first() {
+            Some(header) => *header,
+            None => return,
+        };
 
-        self.last_receive = time();
+        self.now = self.now.max(now);
+        self.last_receive = self.now;
+        self.stats.bytes_received += buff.len() as u64;
+        self.stats.datagrams_received += 1;
 
         if header & ACK_FLAG != 0 {
-            self.handle_ack(buff).await;
+            self.handle_ack(buff);
         } else if header & NACK_FLAG != 0 {
-            self.handle_nack(buff).await;
+            self.handle_nack(buff);
         } else if header & DATAGRAM_FLAG != 0 {
-            self.handle_datagram(buff).await;
+            self.handle_datagram(buff);
+        }
+    }
+    pub fn poll_transmit(&mut self) -> Option<&[u8]> {
+        let len = self.transmits.pop_front()?;
+        let start = self.transmitted;
+        self.transmitted += len;
+        Some(&self.transmit[start..self.transmitted])
+    }
+    pub fn poll_event(&mut self) -> Option<RaknetEvent> {
+        self.events.pop_front()
+    }
+    //id of an answered ping and its round trip time
+    pub fn poll_pong(&mut self) -> Option<(u64, u128)> {
+        self.pongs.pop_front()
+    }
+    //time at which `update` has something to do
+    pub fn next_deadline(&self) -> u128 {
+        if !self.ack_queue.packets.is_empty() || self.packet_queue.has_unsent() {
+            return self.now;
+        }
+        let mut deadline = if self.dissconnected {
+            self.closed_at + CLOSE_TIMEOUT + 1
+        } else {
+            (self.last_receive + TIMEOUT + 1).min(self.last_ping + PING_INTERVAL + 1)
+        };
+        if let Some(resend) = self.packet_queue.next_resend() {
+            deadline = deadline.min(resend);
         }
+        deadline
     }
-    async fn flush_ack(&mut self) {
+    fn flush_ack(&mut self) {
         let acks = self.ack_queue.get_send_able_and_clear();
         for ack in acks {
-            self.send_ack(ack).await;
+            self.send_ack(ack);
         }
     }
-    async fn send_ping(&mut self) {
-        let connected_ping = ConnectedPing::new(self.last_ping as i64);
-        let buff = unwrap_or_dbg!(encode(connected_ping).await);
-        let frame = Frame::new(Reliability::Unreliable, &buff);
-        self.send(frame);
+    //later than the previous ping's even within one millisecond
+    fn send_ping(&mut self) -> i64 {
+        let time_stamp = self.time_stamp().max(self.last_ping_stamp + 1);
+        self.last_ping_stamp = time_stamp;
+        let connected_ping = ConnectedPing::new(time_stamp);
+        let buff = encode(connected_ping);
+        self.send(Frame::new(Reliability::Unreliable, &buff));
+        time_stamp
     }
-    async fn send_ack(&mut self, packet: (u32, u32)) {
-        if self.dissconnected {
-            return;
+    //the answer shows up in `poll_pong` with the returned id
+    pub fn ping(&mut self, now: u128) -> u64 {
+        self.now = self.now.max(now);
+        let time_stamp = self.send_ping();
+        let sent = self.time_stamp();
+        //pings are unreliable, forget the ones whose pong will not come anymore
+        let expired = sent - TIMEOUT as i64;
+        self.pending_pings.retain(|(_, _, sent)| *sent > expired);
+        self.ping_id += 1;
+        self.pending_pings
+            .push_back((self.ping_id, time_stamp, sent));
+        self.ping_id
+    }
+    pub fn remote_clock(&self) -> Option<RemoteClock> {
+        self.clock.get()
+    }
+    pub fn latency(&self) -> Option<Latency> {
+        if self.latency.samples == 0 {
+            None
+        } else {
+            Some(self.latency)
         }
-        let ack = Ack::new(packet);
-        let buff = unwrap_or_dbg!(encode(ack).await);
-        unwrap_or_dbg!(self.socket.send_to(&buff, self.address).await);
     }
-    async fn send_nack(&mut self, packet: u32) {
-        if self.dissconnected {
-            return;
+    fn send_ack(&mut self, packet: (u32, u32)) {
+        self.send_raw(Ack::new(packet));
+    }
+    fn send_nack(&mut self, packet: u32) {
+        self.send_raw(Nack::new((packet, packet)));
+        self.stats.nacks_sent += 1;
+    }
+    fn send_raw<P: Packet>(&mut self, packet: P) {
+        self.reuse_transmit();
+        let start = self.transmit.len();
+        packet.encode_into(&mut self.transmit);
+        let len = self.transmit.len() - start;
+        self.transmits.push_back(len);
+        self.stats.bytes_sent += len as u64;
+        self.stats.datagrams_sent += 1;
+    }
+    //starts the buffer over once everything in it has been polled
+    fn reuse_transmit(&mut self) {
+        if self.transmits.is_empty() {
+            self.transmit.clear();
+            self.transmitted = 0;
         }
-        let nack = Nack::new((packet, packet));
-        let buff = unwrap_or_dbg!(encode(nack).await);
-        unwrap_or_dbg!(self.socket.send_to(&buff, self.address).await);
     }
-    async fn handle_ack(&mut self, buff: &[u8]) {
-        let ack = unwrap_or_return!(decode::<Ack>(buff).await);
+    fn handle_ack(&mut self, buff: &[u8]) {
+        let ack = match decode::<Ack>(buff) {
+            Ok(ack) => ack,
+            Err(_) => return self.close(DisconnectReason::ProtocolError, self.now),
+        };
         for sequence in ack.get_all() {
-            self.packet_queue.received(sequence);
+            self.packet_queue.received(sequence, self.now);
         }
     }
 
-    async fn handle_nack(&mut self, buff: &[u8]) {
-        let nack = unwrap_or_return!(decode::<Nack>(buff).await);
+    fn handle_nack(&mut self, buff: &[u8]) {
+        let nack = match decode::<Nack>(buff) {
+            Ok(nack) => nack,
+            Err(_) => return self.close(DisconnectReason::ProtocolError, self.now),
+        };
+        self.stats.nacks_received += 1;
         for sequence in nack.get_all() {
             self.packet_queue.resend(sequence);
         }
     }
 
-    async fn handle_datagram(&mut self, buff: &[u8]) {
-        let frame_set = unwrap_or_return!(FrameSet::decode(buff).await);
+    fn handle_datagram(&mut self, buff: &[u8]) {
+        let frame_set = match FrameSet::decode(buff) {
+            Ok(frame_set) => frame_set,
+            Err(_) => return self.close(DisconnectReason::ProtocolError, self.now),
+        };
         self.ack_queue.add(frame_set.sequence_number);
         if self.ack_queue.get_missing_len() != 0 {
             for miss in self.ack_queue.get_missing() {
-                self.send_nack(miss).await;
+                self.send_nack(miss);
             }
         }
         for frame in frame_set.datas {
-            self.receive_packet(frame).await;
+            self.receive_packet(frame);
         }
     }
-    async fn receive_packet(&mut self, frame: Frame) {
+    fn receive_packet(&mut self, frame: Frame<'_>) {
         if !frame.reliability.sequenced_or_ordered() {
-            self.handle_packet(&frame.data).await;
+            self.handle_packet(&frame.data);
+            return;
+        }
+        //only frames that wait for others are copied out of the datagram
+        if self.received.take_next(&frame) {
+            self.handle_packet(&frame.data);
         } else {
             self.received.add(frame);
-            for packet in self.received.get_all() {
-                self.handle_packet(&packet.data).await;
-            }
+        }
+        for packet in self.received.get_all() {
+            self.handle_packet(&packet.data);
         }
     }
 
-    async fn handle_packet(&mut self, payload: &[u8]) {
-        match payload[0] {
+    fn handle_packet(&mut self, payload: &[u8]) {
+        let id = match payload.first() {
+            Some(id) => *id,
+            None => return self.close(DisconnectReason::ProtocolError, self.now),
+        };
+        match id {
             ConnectionRequest::ID => {
-                self.handle_connectionrequest(payload).await;
+                self.handle_connectionrequest(payload);
             }
             ConnectionRequestAccepted::ID => {
-                self.handle_connectionrequest_accepted(payload).await;
+                self.handle_connectionrequest_accepted(payload);
             }
             NewIncomingConnection::ID => {}
             ConnectedPing::ID => {
-                self.handle_connectedping(payload).await;
+                self.handle_connectedping(payload);
+            }
+            ConnectedPong::ID => {
+                self.handle_connectedpong(payload);
             }
-            ConnectedPong::ID => {}
             Disconnected::ID => {
-                self.disconnect();
-                self.disconnected(DisconnectReason::Disconnect).await;
+                self.disconnected(DisconnectReason::ClosedByPeer);
             }
             _ => {
                 let rak_packet =
                     RaknetPacket::new(self.address, self.opponent_guid, payload.to_vec());
-                if self.put_event(RaknetEvent::Packet(rak_packet)) {
-                    self.recovery_queue
-                        .push_back(RaknetEvent::Packet(RaknetPacket::new(
-                            self.address,
-                            self.opponent_guid,
-                            payload.to_vec(),
-                        )));
-                }
+                self.put_event(RaknetEvent::Packet(rak_packet));
             }
         }
     }
@@ -221,7 +336,7 @@ impl Connection {
         } else {
             let max = self.mtu - 52 - 100;
             let mut split_len = buff.len() as u16 / max;
-            if buff.len() as u16 % max != 0 {
+            if !(buff.len() as u16).is_multiple_of(max) {
                 split_len += 1;
             }
             for i in 0..split_len {
@@ -246,66 +361,101 @@ impl Connection {
             self.order_index += 1;
         }
     }
-    fn send(&mut self, packet: Frame) {
+    fn send(&mut self, packet: Frame<'static>) {
         self.packet_queue.add_frame(packet);
     }
-    fn put_event(&mut self, event: RaknetEvent) -> bool {
-        self.recovery();
-        self.event_sender.try_send(event).is_err()
+    fn put_event(&mut self, event: RaknetEvent) {
+        if self.dissconnected {
+            return;
+        }
+        self.events.push_back(event);
     }
-    async fn flush_queue(&mut self) {
-        let time = time();
-        for send_able in self.packet_queue.get_packet(time).clone() {
-            let frame_set = unwrap_or_dbg!(send_able.encode().await);
-            unwrap_or_dbg!(self.socket.send_to(&frame_set, self.address).await);
+    fn flush_queue(&mut self) {
+        self.reuse_transmit();
+        for send_able in self.packet_queue.get_packet(self.now) {
+            let start = self.transmit.len();
+            send_able.encode_into(&mut self.transmit);
+            let len = self.transmit.len() - start;
+            self.transmits.push_back(len);
+            self.stats.bytes_sent += len as u64;
+            self.stats.datagrams_sent += 1;
+            self.stats.frame_sets_sent += 1;
         }
     }
-    async fn handle_connectionrequest(&mut self, payload: &[u8]) {
+    fn handle_connectionrequest(&mut self, payload: &[u8]) {
         if let RaknetType::Client = self.rak_type {
             return;
         }
 
-        let p = unwrap_or_return!(decode::<ConnectionRequest>(payload).await);
+        let p = unwrap_or_return!(decode::<ConnectionRequest>(payload));
 
         let reply = ConnectionRequestAccepted::new(self.address, p.time, self.time_stamp());
-        let buff = unwrap_or_dbg!(encode::<ConnectionRequestAccepted>(reply).await);
+        let buff = encode::<ConnectionRequestAccepted>(reply);
         let frame = Frame::new(Reliability::ReliableOrdered, &buff);
         self.send(frame);
         self.message_index += 1;
         self.order_index += 1;
-        if self.put_event(RaknetEvent::Connected(self.address, self.opponent_guid)) {
-            self.recovery_queue
-                .push_back(RaknetEvent::Connected(self.address, self.opponent_guid));
+        if !self.connected {
+            self.connected = true;
+            self.put_event(RaknetEvent::Connected(self.address, self.opponent_guid));
         }
     }
-    async fn handle_connectionrequest_accepted(&mut self, payload: &[u8]) {
+    fn handle_connectionrequest_accepted(&mut self, payload: &[u8]) {
         if let RaknetType::Server = self.rak_type {
             return;
         }
 
-        let accepted = unwrap_or_return!(decode::<ConnectionRequestAccepted>(payload).await);
+        let accepted = unwrap_or_return!(decode::<ConnectionRequestAccepted>(payload));
+        self.clock.add(
+            accepted.request_timestamp,
+            accepted.accepted_timestamp,
+            self.time_stamp(),
+        );
 
         let newincoming = NewIncomingConnection {
             server_address: self.address,
             request_timestamp: accepted.request_timestamp,
             accepted_timestamp: accepted.accepted_timestamp,
         };
-        let buff = unwrap_or_dbg!(encode(newincoming).await);
+        let buff = encode(newincoming);
         let frame = Frame::new(Reliability::ReliableOrdered, &buff);
         self.send(frame);
         self.message_index += 1;
         self.order_index += 1;
-        if self.put_event(RaknetEvent::Connected(self.address, self.opponent_guid)) {
-            self.recovery_queue
-                .push_back(RaknetEvent::Connected(self.address, self.opponent_guid));
+        if !self.connected {
+            self.connected = true;
+            self.put_event(RaknetEvent::Connected(self.address, self.opponent_guid));
+        }
+        self.send_ping();
+    }
+    fn handle_connectedpong(&mut self, payload: &[u8]) {
+        let p = unwrap_or_return!(decode::<ConnectedPong>(payload));
+        let now = self.time_stamp();
+        //a timestamp may run ahead of the clock when several pings went out in one millisecond
+        let pending = self
+            .pending_pings
+            .iter()
+            .position(|(_, stamp, _)| *stamp == p.client_timestamp)
+            .and_then(|i| self.pending_pings.remove(i));
+        let sent = match pending {
+            Some((_, _, sent)) => sent,
+            None => p.client_timestamp.min(now),
+        };
+        let rtt = match now.checked_sub(sent) {
+            Some(rtt) if rtt >= 0 => rtt as u128,
+            _ => return,
+        };
+        self.latency.add(rtt);
+        self.clock.add(sent, p.server_timestamp, now);
+        if let Some((id, _, _)) = pending {
+            self.pongs.push_back((id, rtt));
         }
-        self.send_ping().await;
     }
-    async fn handle_connectedping(&mut self, payload: &[u8]) {
-        let p = unwrap_or_return!(decode::<ConnectedPing>(payload).await);
+    fn handle_connectedping(&mut self, payload: &[u8]) {
+        let p = unwrap_or_return!(decode::<ConnectedPing>(payload));
 
         let pong = ConnectedPong::new(p.client_timestamp, self.time_stamp());
-        let buff = unwrap_or_dbg!(encode(pong).await);
+        let buff = encode(pong);
 
         let mut frame = Frame::new(Reliability::ReliableOrdered, &buff);
         frame.message_index = self.message_index;
@@ -315,7 +465,9 @@ impl Connection {
         self.send(frame);
     }
 
-    pub fn disconnect(&mut self) {
+    //tells the peer and reports the reason locally, once
+    pub fn close(&mut self, reason: DisconnectReason, now: u128) {
+        self.now = self.now.max(now);
         if !self.dissconnected {
             let mut frame = Frame::new(Reliability::ReliableOrdered, &[Disconnected::ID]);
             frame.message_index = self.message_index;
@@ -323,26 +475,211 @@ impl Connection {
             self.message_index += 1;
             self.order_index += 1;
             self.send(frame);
-            self.dissconnected = true;
+            self.disconnected(reason);
         }
     }
 
-    async fn disconnected(&mut self, reason: DisconnectReason) {
+    pub fn has_pending(&self) -> bool {
+        self.packet_queue.has_pending()
+    }
+
+    //closed locally or by the peer, it may still be sending what is left
+    pub fn is_closed(&self) -> bool {
+        self.dissconnected
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected && !self.dissconnected
+    }
+
+    //closed and either everything has been acknowledged or the peer gave up on it
+    pub fn is_finished(&self) -> bool {
+        self.dissconnected && (!self.has_pending() || self.now - self.closed_at > CLOSE_TIMEOUT)
+    }
+
+    fn disconnected(&mut self, reason: DisconnectReason) {
+        if self.dissconnected {
+            return;
+        }
         self.dissconnected = true;
-        if self.put_event(RaknetEvent::Disconnected(
+        self.closed_at = self.now;
+        self.events.push_back(RaknetEvent::Disconnected(
             self.address,
             self.opponent_guid,
             reason,
-        )) {
-            self.recovery_queue.push_back(RaknetEvent::Disconnected(
-                self.address,
-                self.guid,
-                reason,
-            ));
+        ));
+    }
+
+    pub fn stats(&self) -> RaknetStatistics {
+        let mut stats = self.stats;
+        stats.retransmissions = self.packet_queue.resent;
+        if stats.frame_sets_sent != 0 {
+            stats.packet_loss = stats.retransmissions as f32 / stats.frame_sets_sent as f32;
         }
+        stats.rtt = self.packet_queue.rtt;
+        stats.unacknowledged_bytes = self.packet_queue.unacknowledged_bytes();
+        stats.queued_frames = self.packet_queue.queued_frames();
+        stats.split_reassemblies = self.received.split_reassemblies();
+        stats.missing_datagrams = self.ack_queue.get_missing_len();
+        stats
     }
 
-    pub fn time_stamp(&self) -> i64 {
-        time().try_into().unwrap_or(0)
+    fn time_stamp(&self) -> i64 {
+        self.now.try_into().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod connection_test {
+    use super::{Connection, RaknetType};
+    use crate::{DisconnectReason, RaknetEvent};
+
+    fn pair(now: u128) -> (Connection, Connection) {
+        let server_addr = "127.0.0.1:19132".parse().unwrap();
+        let client_addr = "127.0.0.1:50000".parse().unwrap();
+        let mut client = Connection::new(server_addr, 1, 2, 1492, RaknetType::Client, now);
+        let server = Connection::new(client_addr, 2, 1, 1492, RaknetType::Server, now);
+        client.connect(now);
+        (client, server)
+    }
+
+    //hands over everything `from` has to send, `lose` picks the datagrams that get lost
+    fn deliver(
+        from: &mut Connection,
+        to: &mut Connection,
+        now: u128,
+        mut lose: impl FnMut(&[u8]) -> bool,
+    ) {
+        from.update(now);
+        while let Some(datagram) = from.poll_transmit() {
+            if !lose(datagram) {
+                to.handle(datagram, now);
+            }
+        }
+    }
+
+    fn events(conn: &mut Connection) -> Vec<RaknetEvent> {
+        std::iter::from_fn(|| conn.poll_event()).collect()
+    }
+
+    #[test]
+    fn handshake_and_retransmission() {
+        let mut now = 1000;
+        let (mut client, mut server) = pair(now);
+        for _ in 0..3 {
+            deliver(&mut client, &mut server, now, |_| false);
+            deliver(&mut server, &mut client, now, |_| false);
+        }
+        assert!(matches!(
+            events(&mut client)[..],
+            [RaknetEvent::Connected(..)]
+        ));
+        assert!(matches!(
+            events(&mut server)[..],
+            [RaknetEvent::Connected(..)]
+        ));
+
+        //the first copy is lost, the resend timer brings it over
+        client.send_to(b"\xfehello");
+        let mut lost = false;
+        deliver(&mut client, &mut server, now, |_| {
+            !std::mem::replace(&mut lost, true)
+        });
+        assert!(events(&mut server).is_empty());
+        assert!(client.next_deadline() > now);
+
+        now = client.next_deadline();
+        deliver(&mut client, &mut server, now, |_| false);
+        match &events(&mut server)[..] {
+            [RaknetEvent::Packet(packet)] => assert_eq!(packet.data, b"\xfehello"),
+            _ => panic!("the message was not retransmitted"),
+        }
+        let stats = client.stats();
+        assert_eq!(stats.retransmissions, 1);
+        //the acknowledgements the client sent do not dilute the loss
+        assert!(stats.datagrams_sent > stats.frame_sets_sent);
+        assert_eq!(stats.packet_loss, 1.0 / stats.frame_sets_sent as f32);
+    }
+
+    #[test]
+    fn pings_in_one_millisecond() {
+        let now = 1000;
+        let (mut client, mut server) = pair(now);
+        for _ in 0..3 {
+            deliver(&mut client, &mut server, now, |_| false);
+            deliver(&mut server, &mut client, now, |_| false);
+        }
+        let first = client.ping(now);
+        let second = client.ping(now);
+        assert_ne!(first, second);
+        deliver(&mut client, &mut server, now, |_| false);
+        deliver(&mut server, &mut client, now, |_| false);
+        let pongs: Vec<u64> = std::iter::from_fn(|| client.poll_pong())
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(pongs, [first, second]);
+    }
+
+    #[test]
+    fn first_ping_of_a_millisecond_lost() {
+        let now = 1000;
+        let (mut client, mut server) = pair(now);
+        for _ in 0..3 {
+            deliver(&mut client, &mut server, now, |_| false);
+            deliver(&mut server, &mut client, now, |_| false);
+        }
+        client.ping(now);
+        deliver(&mut client, &mut server, now, |_| true);
+        let second = client.ping(now);
+        deliver(&mut client, &mut server, now, |_| false);
+        deliver(&mut server, &mut client, now, |_| false);
+        let pongs: Vec<u64> = std::iter::from_fn(|| client.poll_pong())
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(pongs, [second]);
+    }
+
+    #[test]
+    fn empty_frame() {
+        let now = 1000;
+        let (mut client, mut server) = pair(now);
+        for _ in 0..3 {
+            deliver(&mut client, &mut server, now, |_| false);
+            deliver(&mut server, &mut client, now, |_| false);
+        }
+        events(&mut server);
+        //an unreliable frame without a body
+        server.handle(&[0x84, 0, 0, 0, 0x00, 0x00, 0x00], now);
+        assert!(matches!(
+            events(&mut server)[..],
+            [RaknetEvent::Disconnected(
+                _,
+                _,
+                DisconnectReason::ProtocolError
+            )]
+        ));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut now = 1000;
+        let (mut client, _server) = pair(now);
+        loop {
+            client.update(now);
+            while client.poll_transmit().is_some() {}
+            if client.is_finished() {
+                break;
+            }
+            now = client.next_deadline();
+        }
+        assert!(matches!(
+            events(&mut client)[..],
+            [RaknetEvent::Disconnected(
+                _,
+                _,
+                DisconnectReason::HandshakeFailed
+            )]
+        ));
+        assert!(now > 1000 + super::TIMEOUT);
     }
 }
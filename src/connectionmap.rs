@@ -0,0 +1,93 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::mpsc::Sender;
+
+use crate::{driver::ConnectionHandle, transport::normalise};
+
+const SHARDS: usize = 32;
+
+type Shard = HashMap<SocketAddr, (Arc<ConnectionHandle>, Sender<Vec<u8>>)>;
+
+//connections split over independently locked shards,
+//a lock is only held for the lookup and never across an await
+pub(crate) struct ConnectionMap {
+    shards: Vec<Mutex<Shard>>,
+}
+
+impl ConnectionMap {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, addr: &SocketAddr) -> MutexGuard<'_, Shard> {
+        let mut hasher = DefaultHasher::new();
+        addr.hash(&mut hasher);
+        let shard = &self.shards[hasher.finish() as usize % SHARDS];
+        shard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    //callers may name IPv4 peers by their mapped IPv6 form
+    pub fn get(&self, addr: &SocketAddr) -> Option<Arc<ConnectionHandle>> {
+        let addr = &normalise(*addr);
+        self.shard(addr).get(addr).map(|(conn, _)| conn.clone())
+    }
+
+    //feeds datagrams to the connection's driver
+    pub fn inbound(&self, addr: &SocketAddr) -> Option<Sender<Vec<u8>>> {
+        self.shard(addr)
+            .get(addr)
+            .map(|(_, inbound)| inbound.clone())
+    }
+
+    //false if the address already has a connection
+    pub fn insert(
+        &self,
+        addr: SocketAddr,
+        conn: Arc<ConnectionHandle>,
+        inbound: Sender<Vec<u8>>,
+    ) -> bool {
+        let mut shard = self.shard(&addr);
+        if shard.contains_key(&addr) {
+            return false;
+        }
+        shard.insert(addr, (conn, inbound));
+        true
+    }
+
+    pub fn remove(&self, addr: &SocketAddr) {
+        let addr = &normalise(*addr);
+        self.shard(addr).remove(addr);
+    }
+
+    pub fn values(&self) -> Vec<Arc<ConnectionHandle>> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+                shard
+                    .values()
+                    .map(|(conn, _)| conn.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()).is_empty())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot, watch, Notify};
+
+use crate::{
+    connection::Connection,
+    eventqueue::{EventSender, SendError},
+    time,
+    transport::{outbound, Transport},
+    DisconnectReason, Latency, RaknetEvent, RaknetStatistics, RemoteClock,
+};
+
+//datagrams waiting for a busy connection, further ones are dropped like a full socket buffer
+pub(crate) const INBOUND_CAPACITY: usize = 256;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) async fn wait_pong(receiver: oneshot::Receiver<u128>) -> std::io::Result<u128> {
+    match tokio::time::timeout(PING_TIMEOUT, receiver).await {
+        Ok(Ok(rtt)) => Ok(rtt),
+        Ok(Err(_)) => Err(std::io::Error::new(
+            std::io::ErrorKind::NotConnected,
+            "Not connected",
+        )),
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "No pong received",
+        )),
+    }
+}
+
+struct State {
+    conn: Connection,
+    ping_waiters: Vec<(u64, oneshot::Sender<u128>)>,
+}
+
+//what the endpoints share with the driver of one connection,
+//the lock is only held while the core runs and never across an await
+pub(crate) struct ConnectionHandle {
+    state: Mutex<State>,
+    wake: Notify,
+    events: EventSender,
+    //a timeout is left to the reconnect policy instead of being reported
+    resumable: bool,
+    //the connection replaces one that timed out, it reports `Reconnected` instead of `Connected`
+    //and a failed handshake is left to the reconnect policy as well
+    resumed: bool,
+    connected: AtomicBool,
+    lost: AtomicBool,
+    //the driver returned, the connection is finished or its owner went away
+    stopped: AtomicBool,
+    //fires when the connection comes up or the driver stops
+    changed: Notify,
+    //the slot the connection takes from its listener
+    slots: Option<Arc<Slots>>,
+    //datagrams the transport failed to send, see `RaknetStatistics::send_errors`
+    send_errors: AtomicU64,
+    pub address: SocketAddr,
+}
+
+impl ConnectionHandle {
+    pub fn new(conn: Connection, events: EventSender) -> Self {
+        Self {
+            address: conn.address,
+            state: Mutex::new(State {
+                conn,
+                ping_waiters: vec![],
+            }),
+            wake: Notify::new(),
+            events,
+            resumable: false,
+            resumed: false,
+            connected: AtomicBool::new(false),
+            lost: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            changed: Notify::new(),
+            slots: None,
+            send_errors: AtomicU64::new(0),
+        }
+    }
+
+    //the listener reserved a slot in `slots` for the connection
+    pub fn counted(mut self, slots: Arc<Slots>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    pub fn resumable(mut self, resumed: bool) -> Self {
+        self.resumable = true;
+        self.resumed = resumed;
+        self
+    }
+
+    //the connection went away silently and the reconnect policy takes over
+    pub fn lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    //true once the connection is up, false if the driver stopped before
+    pub async fn wait_connected(&self) -> bool {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            if self.connected() {
+                return true;
+            }
+            if self.stopped.load(Ordering::Relaxed) {
+                return false;
+            }
+            changed.await;
+        }
+    }
+
+    pub fn opponent_guid(&self) -> u64 {
+        self.state().conn.opponent_guid
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn send(&self, buff: &[u8]) {
+        self.state().conn.send_to(buff);
+        self.wake.notify_one();
+    }
+
+    pub fn close(&self, reason: DisconnectReason) {
+        self.state().conn.close(reason, time());
+        self.wake.notify_one();
+    }
+
+    //resolves with the round trip time once the pong arrives
+    pub fn ping(&self) -> oneshot::Receiver<u128> {
+        let (sender, receiver) = oneshot::channel();
+        let mut state = self.state();
+        let id = state.conn.ping(time());
+        state.ping_waiters.retain(|(_, waiter)| !waiter.is_closed());
+        state.ping_waiters.push((id, sender));
+        drop(state);
+        self.wake.notify_one();
+        receiver
+    }
+
+    pub fn stats(&self) -> RaknetStatistics {
+        let mut stats = self.state().conn.stats();
+        stats.dropped_events = self.events.dropped();
+        stats.send_errors = self.send_errors.load(Ordering::Relaxed);
+        stats
+    }
+
+    pub fn latency(&self) -> Option<Latency> {
+        self.state().conn.latency()
+    }
+
+    pub fn remote_clock(&self) -> Option<RemoteClock> {
+        self.state().conn.remote_clock()
+    }
+
+    pub fn dropped_events(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state().conn.is_finished()
+    }
+}
+
+//tells the waiters of `wait_connected` however the driver returns or is dropped
+struct Stopped<'a>(&'a ConnectionHandle);
+
+impl Drop for Stopped<'_> {
+    fn drop(&mut self) {
+        self.0.stopped.store(true, Ordering::Relaxed);
+        self.0.changed.notify_waiters();
+    }
+}
+
+//connections of a listener against its limit, a slot is reserved when a handshake
+//is admitted and given back when the connection goes away
+#[derive(Default)]
+pub(crate) struct Slots {
+    //half-open handshakes
+    reserved: AtomicUsize,
+    established: AtomicUsize,
+}
+
+impl Slots {
+    //the connections that completed the handshake
+    pub fn established(&self) -> usize {
+        self.established.load(Ordering::SeqCst)
+    }
+
+    pub fn taken(&self) -> usize {
+        self.reserved.load(Ordering::SeqCst) + self.established()
+    }
+
+    pub fn reserve(&self) {
+        self.reserved.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn establish(&self) {
+        self.established.fetch_add(1, Ordering::SeqCst);
+        self.reserved.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn release(&self, established: bool) {
+        match established {
+            true => self.established.fetch_sub(1, Ordering::SeqCst),
+            false => self.reserved.fetch_sub(1, Ordering::SeqCst),
+        };
+    }
+}
+
+//runs its closure however the task holding it ends, unwinding and aborting included
+pub(crate) struct Cleanup<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Cleanup<F> {
+    pub fn new(cleanup: F) -> Self {
+        Self(Some(cleanup))
+    }
+}
+
+impl<F: FnOnce()> Drop for Cleanup<F> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.0.take() {
+            cleanup();
+        }
+    }
+}
+
+//runs the connection until it is finished or the owner goes away,
+//it only wakes up when something is due
+pub(crate) async fn drive<T: Transport>(
+    handle: Arc<ConnectionHandle>,
+    socket: Arc<T>,
+    mut inbound: mpsc::Receiver<Vec<u8>>,
+    mut stop: watch::Receiver<()>,
+) {
+    let _stopped = Stopped(&handle);
+    let target = match socket.local_addr() {
+        Ok(local) => outbound(handle.address, &local),
+        Err(_) => handle.address,
+    };
+    //datagrams are copied out of the core into one reused buffer before the lock is released
+    let mut outgoing: Vec<u8> = vec![];
+    let mut lengths: Vec<usize> = vec![];
+    loop {
+        let (events, deadline, finished) = {
+            let mut state = handle.state();
+            let State { conn, ping_waiters } = &mut *state;
+            conn.update(time());
+            outgoing.clear();
+            lengths.clear();
+            while let Some(datagram) = conn.poll_transmit() {
+                outgoing.extend_from_slice(datagram);
+                lengths.push(datagram.len());
+            }
+            let events: Vec<RaknetEvent> = std::iter::from_fn(|| conn.poll_event()).collect();
+            while let Some((id, rtt)) = conn.poll_pong() {
+                if let Some(i) = ping_waiters.iter().position(|(waiter, _)| *waiter == id) {
+                    let (_, waiter) = ping_waiters.swap_remove(i);
+                    let _ = waiter.send(rtt);
+                }
+            }
+            (events, conn.next_deadline(), conn.is_finished())
+        };
+        let mut start = 0;
+        for len in lengths.iter() {
+            if socket
+                .send_to(&outgoing[start..start + len], target)
+                .await
+                .is_err()
+            {
+                handle.send_errors.fetch_add(1, Ordering::Relaxed);
+            }
+            start += len;
+        }
+        //the connection may have changed while the application was catching up
+        let mut waited = false;
+        for event in events {
+            let event = match event {
+                RaknetEvent::Disconnected(_, _, reason)
+                    if handle.resumable
+                        && (reason == DisconnectReason::Timeout
+                            || handle.resumed && !handle.connected()) =>
+                {
+                    handle.lost.store(true, Ordering::Relaxed);
+                    continue;
+                }
+                RaknetEvent::Connected(address, guid) => {
+                    handle.connected.store(true, Ordering::Relaxed);
+                    handle.changed.notify_waiters();
+                    if let Some(slots) = &handle.slots {
+                        slots.establish();
+                    }
+                    match handle.resumed {
+                        true => RaknetEvent::Reconnected(address, guid),
+                        false => RaknetEvent::Connected(address, guid),
+                    }
+                }
+                event => event,
+            };
+            if let RaknetEvent::Disconnected(..) = event {
+                handle.events.force_send(event);
+                continue;
+            }
+            let mut event = event;
+            loop {
+                event = match handle.events.try_send(event) {
+                    Ok(()) => break,
+                    //a closing connection no longer waits for the application
+                    Err(SendError::Full(event)) if handle.state().conn.is_closed() => {
+                        handle.events.force_send(event);
+                        break;
+                    }
+                    Err(SendError::Full(event)) => event,
+                    Err(SendError::Overflow) => {
+                        handle.close(DisconnectReason::Overflow);
+                        break;
+                    }
+                };
+                waited = true;
+                tokio::select! {
+                    _ = handle.events.not_full() => {}
+                    _ = handle.wake.notified() => {}
+                    _ = stop.changed() => return,
+                }
+            }
+        }
+        if finished {
+            return;
+        }
+        if waited {
+            continue;
+        }
+        let delay = Duration::from_millis(deadline.saturating_sub(time()) as u64);
+        tokio::select! {
+            buff = inbound.recv() => {
+                let buff = match buff {
+                    Some(buff) => buff,
+                    None => return,
+                };
+                let mut state = handle.state();
+                state.conn.handle(&buff, time());
+                //handle whatever arrived meanwhile before acknowledging
+                while let Ok(buff) = inbound.try_recv() {
+                    state.conn.handle(&buff, time());
+                }
+            }
+            _ = handle.wake.notified() => {}
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop.changed() => return,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::Notify;
+
+use crate::RaknetEvent;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    //stop handling the peer's packets until the application catches up
+    Block,
+    DropOldest,
+    Disconnect,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EventQueueConfig {
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl Default for EventQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            policy: OverflowPolicy::Block,
+        }
+    }
+}
+
+pub(crate) enum SendError {
+    //the queue is full and the policy is to wait, the event is handed back
+    Full(RaknetEvent),
+    //the event was dropped and the policy asks to disconnect the peer
+    Overflow,
+}
+
+struct State {
+    events: VecDeque<RaknetEvent>,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    config: EventQueueConfig,
+    not_empty: Notify,
+    not_full: Notify,
+    dropped: AtomicU64,
+}
+
+pub(crate) struct EventSender {
+    shared: Arc<Shared>,
+}
+
+pub(crate) struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+pub(crate) fn event_queue(config: EventQueueConfig) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            events: VecDeque::new(),
+            sender_closed: false,
+            receiver_closed: false,
+        }),
+        config: EventQueueConfig {
+            capacity: config.capacity.max(1),
+            policy: config.policy,
+        },
+        not_empty: Notify::new(),
+        not_full: Notify::new(),
+        dropped: AtomicU64::new(0),
+    });
+    (
+        EventSender {
+            shared: shared.clone(),
+        },
+        EventReceiver { shared },
+    )
+}
+
+impl Shared {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl EventSender {
+    //never waits, a blocked event is handed back to retry after `not_full`
+    pub fn try_send(&self, event: RaknetEvent) -> Result<(), SendError> {
+        let mut state = self.shared.state();
+        if state.receiver_closed {
+            return Ok(());
+        }
+        if state.events.len() < self.shared.config.capacity {
+            state.events.push_back(event);
+            self.shared.not_empty.notify_one();
+            return Ok(());
+        }
+        match self.shared.config.policy {
+            OverflowPolicy::Block => Err(SendError::Full(event)),
+            OverflowPolicy::DropOldest => {
+                state.events.pop_front();
+                state.events.push_back(event);
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            OverflowPolicy::Disconnect => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(SendError::Overflow)
+            }
+        }
+    }
+
+    //resolves once the application took an event or went away
+    pub async fn not_full(&self) {
+        self.shared.not_full.notified().await
+    }
+
+    //ignores the capacity, used for the last event of a connection
+    pub fn force_send(&self, event: RaknetEvent) {
+        let mut state = self.shared.state();
+        if !state.receiver_closed {
+            state.events.push_back(event);
+            self.shared.not_empty.notify_one();
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.shared.state().sender_closed = true;
+        self.shared.not_empty.notify_one();
+    }
+}
+
+impl EventReceiver {
+    pub async fn recv(&mut self) -> Option<RaknetEvent> {
+        loop {
+            {
+                let mut state = self.shared.state();
+                if let Some(event) = state.events.pop_front() {
+                    self.shared.not_full.notify_one();
+                    return Some(event);
+                }
+                if state.sender_closed {
+                    return None;
+                }
+            }
+            self.shared.not_empty.notified().await;
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Option<RaknetEvent> {
+        let event = self.shared.state().events.pop_front();
+        if event.is_some() {
+            self.shared.not_full.notify_one();
+        }
+        event
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.state().receiver_closed = true;
+        self.shared.not_full.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod event_queue_test {
+    use super::{event_queue, EventQueueConfig, EventSender, OverflowPolicy, SendError};
+    use crate::RaknetEvent;
+
+    //waits for room like the driver does, false means the peer has to be disconnected
+    async fn send(sender: &EventSender, mut event: RaknetEvent) -> bool {
+        loop {
+            event = match sender.try_send(event) {
+                Ok(()) => return true,
+                Err(SendError::Full(event)) => event,
+                Err(SendError::Overflow) => return false,
+            };
+            sender.not_full().await;
+        }
+    }
+
+    fn connected(guid: u64) -> RaknetEvent {
+        RaknetEvent::Connected("127.0.0.1:19132".parse().unwrap(), guid)
+    }
+
+    fn guid(event: RaknetEvent) -> u64 {
+        match event {
+            RaknetEvent::Connected(_, guid) => guid,
+            _ => panic!("unexpected event"),
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let (sender, mut receiver) = event_queue(EventQueueConfig {
+            capacity: 2,
+            policy: OverflowPolicy::DropOldest,
+        });
+        for i in 0..4 {
+            assert!(send(&sender, connected(i)).await);
+        }
+        assert_eq!(guid(receiver.try_recv().unwrap()), 2);
+        assert_eq!(guid(receiver.try_recv().unwrap()), 3);
+        assert!(receiver.try_recv().is_none());
+        assert_eq!(receiver.dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn disconnect() {
+        let (sender, mut receiver) = event_queue(EventQueueConfig {
+            capacity: 1,
+            policy: OverflowPolicy::Disconnect,
+        });
+        assert!(send(&sender, connected(0)).await);
+        assert!(!send(&sender, connected(1)).await);
+        sender.force_send(connected(2));
+        assert_eq!(guid(receiver.try_recv().unwrap()), 0);
+        assert_eq!(guid(receiver.try_recv().unwrap()), 2);
+        assert_eq!(sender.dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn block() {
+        let (sender, mut receiver) = event_queue(EventQueueConfig {
+            capacity: 1,
+            policy: OverflowPolicy::Block,
+        });
+        let task = tokio::spawn(async move {
+            for i in 0..3 {
+                assert!(send(&sender, connected(i)).await);
+            }
+        });
+        for i in 0..3 {
+            assert_eq!(guid(receiver.recv().await.unwrap()), i);
+        }
+        task.await.unwrap();
+        assert!(receiver.recv().await.is_none());
+        assert_eq!(receiver.dropped(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::VecDeque, io, net::SocketAddr};
+
+use crate::{macros::*, packets::*, RaknetError};
+
+pub(crate) const RAKNET_PROTOCOL_VERSION: u8 = 0xA;
+
+/// The offline part of connecting, `OpenConnectionRequest1` and 2, without any IO or clock.
+///
+/// Hand it the datagrams that came from `remote` with `handle` and send what
+/// `poll_transmit` returns until `poll_outcome` has the server's guid or the reason
+/// it refused. The connected part then continues in a `Connection`.
+pub(crate) struct Handshake {
+    remote: SocketAddr,
+    guid: u64,
+    transmits: VecDeque<Vec<u8>>,
+    outcome: Option<Result<u64, RaknetError>>,
+}
+
+impl Handshake {
+    pub fn new(remote: SocketAddr, guid: u64, mtu: u16) -> Self {
+        let request1 = OpenConnectionRequest1::new(RAKNET_PROTOCOL_VERSION, mtu);
+        Self {
+            remote,
+            guid,
+            transmits: VecDeque::from([encode(request1)]),
+            outcome: None,
+        }
+    }
+
+    pub fn handle(&mut self, buff: &[u8]) {
+        if buff.is_empty() || self.outcome.is_some() {
+            return;
+        }
+        let remote = self.remote;
+        match buff[0] {
+            OpenConnectionReply1::ID => {
+                let reply1 = unwrap_or_return!(decode::<OpenConnectionReply1>(buff));
+                let request2 = OpenConnectionRequest2::new(remote, reply1.mtu_size, self.guid);
+                self.transmits.push_back(encode(request2));
+            }
+            OpenConnectionReply2::ID => {
+                let reply2 = unwrap_or_return!(decode::<OpenConnectionReply2>(buff));
+                self.outcome = Some(Ok(reply2.guid));
+            }
+            IncompatibleProtocolVersion::ID => {
+                let version = unwrap_or_return!(decode::<IncompatibleProtocolVersion>(buff));
+                self.outcome = Some(Err(RaknetError::IncompatibleProtocolVersion(
+                    version.server_protocol,
+                    RAKNET_PROTOCOL_VERSION,
+                )));
+            }
+            ConnectionBanned::ID => {
+                self.outcome = Some(Err(RaknetError::ConnectionBanned(remote)));
+            }
+            NoFreeIncomingConnections::ID => {
+                self.outcome = Some(Err(RaknetError::NoFreeIncomingConnections(remote)));
+            }
+            AlreadyConnected::ID => {
+                unwrap_or_return!(decode::<AlreadyConnected>(buff));
+                self.outcome = Some(Err(RaknetError::AlreadyConnected(remote)));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.transmits.pop_front()
+    }
+
+    //the server's guid once it accepted, or why it did not
+    pub fn poll_outcome(&mut self) -> Option<Result<u64, RaknetError>> {
+        self.outcome.take()
+    }
+}
+
+//the answers to the requests of a `Handshake`
+pub(crate) fn is_reply(id: u8) -> bool {
+    matches!(
+        id,
+        OpenConnectionReply1::ID
+            | OpenConnectionReply2::ID
+            | IncompatibleProtocolVersion::ID
+            | ConnectionBanned::ID
+            | NoFreeIncomingConnections::ID
+            | AlreadyConnected::ID
+    )
+}
+
+//what the listener knows when a peer asks to connect
+pub(crate) struct Admission<'a> {
+    pub banned: bool,
+    pub full: bool,
+    //guids of the peers that already have a connection
+    pub connected: &'a [u64],
+}
+
+pub(crate) enum Answer {
+    Reply(Vec<u8>),
+    //OpenConnectionReply2, the connection starts with the peer's guid and mtu once it is sent
+    Accept(Vec<u8>, u64, u16),
+}
+
+//the listening side answers both requests without keeping any state
+pub(crate) fn answer_request1(buff: &[u8], guid: u64, banned: bool) -> io::Result<Vec<u8>> {
+    let request1 = decode::<OpenConnectionRequest1>(buff)?;
+    Ok(if banned {
+        encode(ConnectionBanned::new(guid))
+    } else if request1.protocol_version == RAKNET_PROTOCOL_VERSION {
+        encode(OpenConnectionReply1::new(guid, false, request1.mtu_size))
+    } else {
+        encode(IncompatibleProtocolVersion::new(
+            RAKNET_PROTOCOL_VERSION,
+            guid,
+        ))
+    })
+}
+
+pub(crate) fn answer_request2(
+    buff: &[u8],
+    source: SocketAddr,
+    guid: u64,
+    admission: &Admission<'_>,
+) -> io::Result<Answer> {
+    let request2 = decode::<OpenConnectionRequest2>(buff)?;
+    Ok(if admission.banned {
+        Answer::Reply(encode(ConnectionBanned::new(guid)))
+    } else if admission.connected.contains(&request2.guid) {
+        Answer::Reply(encode(AlreadyConnected::new(guid)))
+    } else if admission.full {
+        Answer::Reply(encode(NoFreeIncomingConnections::new(guid)))
+    } else {
+        let reply2 = OpenConnectionReply2::new(guid, source, request2.mtu, false);
+        Answer::Accept(encode(reply2), request2.guid, request2.mtu)
+    })
+}
+
+#[cfg(test)]
+mod handshake_test {
+    use super::{answer_request1, answer_request2, Admission, Answer, Handshake};
+    use crate::RaknetError;
+
+    fn admit(full: bool) -> Admission<'static> {
+        Admission {
+            banned: false,
+            full,
+            connected: &[],
+        }
+    }
+
+    #[test]
+    fn accepted() {
+        let server = "127.0.0.1:19132".parse().unwrap();
+        let client = "127.0.0.1:50000".parse().unwrap();
+        let mut handshake = Handshake::new(server, 1, 1492);
+        let request1 = handshake.poll_transmit().unwrap();
+        handshake.handle(&answer_request1(&request1, 2, false).unwrap());
+        let request2 = handshake.poll_transmit().unwrap();
+        match answer_request2(&request2, client, 2, &admit(false)) {
+            Ok(Answer::Accept(reply2, guid, mtu)) => {
+                assert_eq!((guid, mtu), (1, 1492));
+                handshake.handle(&reply2);
+            }
+            _ => panic!("the request was not accepted"),
+        }
+        assert!(handshake.poll_transmit().is_none());
+        assert!(matches!(handshake.poll_outcome(), Some(Ok(2))));
+    }
+
+    #[test]
+    fn refused() {
+        let server = "127.0.0.1:19132".parse().unwrap();
+        let client = "127.0.0.1:50000".parse().unwrap();
+        let mut handshake = Handshake::new(server, 1, 1492);
+        let request1 = handshake.poll_transmit().unwrap();
+        handshake.handle(&answer_request1(&request1, 2, false).unwrap());
+        let request2 = handshake.poll_transmit().unwrap();
+        match answer_request2(&request2, client, 2, &admit(true)) {
+            Ok(Answer::Reply(reply)) => handshake.handle(&reply),
+            _ => panic!("a full server accepted"),
+        }
+        assert!(matches!(
+            handshake.poll_outcome(),
+            Some(Err(RaknetError::NoFreeIncomingConnections(_)))
+        ));
+
+        let mut handshake = Handshake::new(server, 1, 1492);
+        let request1 = handshake.poll_transmit().unwrap();
+        handshake.handle(&answer_request1(&request1, 2, true).unwrap());
+        assert!(matches!(
+            handshake.poll_outcome(),
+            Some(Err(RaknetError::ConnectionBanned(_)))
+        ));
+    }
+}
@@ -1,17 +1,38 @@
 pub(crate) mod client;
-mod connection;
+pub(crate) mod clock;
+pub mod codec;
+pub(crate) mod connection;
+mod connectionmap;
+mod driver;
+pub(crate) mod eventqueue;
+pub(crate) mod handshake;
 pub mod packet;
 mod packetqueue;
 pub mod packets;
+pub(crate) mod peer;
 pub(crate) mod ping;
+pub(crate) mod pong;
+pub(crate) mod reconnect;
 pub(crate) mod server;
 pub use crate::client::*;
+pub use crate::clock::RemoteClock;
+pub use crate::connection::{Connection, RaknetType};
+pub use crate::eventqueue::{EventQueueConfig, OverflowPolicy};
+pub use crate::motd::BedrockMotd;
+pub use crate::peer::*;
 pub use crate::ping::*;
+pub use crate::pong::{bedrock_players, PingInfo};
+pub use crate::reconnect::{QueuedMessages, ReconnectPolicy};
 pub use crate::server::*;
+pub use crate::stats::*;
+pub use crate::transport::Transport;
 pub(crate) mod macros;
+pub(crate) mod motd;
 pub(crate) mod rak;
 pub mod reader;
 mod receivedqueue;
+pub(crate) mod stats;
+pub(crate) mod transport;
 pub mod writer;
 pub use crate::rak::*;
 
@@ -0,0 +1,118 @@
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind, Result},
+    str::FromStr,
+};
+
+/// The pong payload of Bedrock servers,
+/// `MCPE;name;protocol;version;players;max;guid;level;gamemode;gamemode id;port v4;port v6;`.
+///
+/// The first six fields are required, servers leave out the others from the end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BedrockMotd {
+    //MCPE or MCEE for Education Edition
+    pub edition: String,
+    pub name: String,
+    pub protocol: u32,
+    pub version: String,
+    pub players: u32,
+    pub max_players: u32,
+    pub server_guid: Option<u64>,
+    pub level: Option<String>,
+    pub gamemode: Option<String>,
+    pub gamemode_id: Option<u8>,
+    pub port_v4: Option<u16>,
+    pub port_v6: Option<u16>,
+}
+
+fn field<T: FromStr>(fields: &[&str], index: usize, name: &str) -> Result<T> {
+    let value = fields
+        .get(index)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("motd without {}", name)))?;
+    value.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("bad motd {}: {}", name, value),
+        )
+    })
+}
+
+fn optional<T: FromStr>(fields: &[&str], index: usize, name: &str) -> Result<Option<T>> {
+    match fields.get(index) {
+        Some(value) if !value.is_empty() => field(fields, index, name).map(Some),
+        _ => Ok(None),
+    }
+}
+
+impl FromStr for BedrockMotd {
+    type Err = Error;
+
+    fn from_str(motd: &str) -> Result<Self> {
+        let fields: Vec<&str> = motd.split(';').collect();
+        Ok(Self {
+            edition: field(&fields, 0, "edition")?,
+            name: field(&fields, 1, "name")?,
+            protocol: field(&fields, 2, "protocol")?,
+            version: field(&fields, 3, "version")?,
+            players: field(&fields, 4, "players")?,
+            max_players: field(&fields, 5, "max players")?,
+            server_guid: optional(&fields, 6, "server guid")?,
+            level: optional(&fields, 7, "level")?,
+            gamemode: optional(&fields, 8, "gamemode")?,
+            gamemode_id: optional(&fields, 9, "gamemode id")?,
+            port_v4: optional(&fields, 10, "port v4")?,
+            port_v6: optional(&fields, 11, "port v6")?,
+        })
+    }
+}
+
+impl Display for BedrockMotd {
+    //optional fields are written up to the first missing one
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{};{};{};{};{};{};",
+            self.edition, self.name, self.protocol, self.version, self.players, self.max_players
+        )?;
+        let optional = [
+            self.server_guid.map(|guid| guid.to_string()),
+            self.level.clone(),
+            self.gamemode.clone(),
+            self.gamemode_id.map(|id| id.to_string()),
+            self.port_v4.map(|port| port.to_string()),
+            self.port_v6.map(|port| port.to_string()),
+        ];
+        for value in optional.iter().map_while(|value| value.as_ref()) {
+            write!(f, "{};", value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod motd_test {
+    use super::BedrockMotd;
+
+    #[test]
+    fn bedrock_motd() {
+        let raw = "MCPE;§5raknet rs;390;1.17.42;3;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;";
+        let motd: BedrockMotd = raw.parse().unwrap();
+        assert_eq!(motd.name, "§5raknet rs");
+        assert_eq!(motd.protocol, 390);
+        assert_eq!(motd.players, 3);
+        assert_eq!(motd.max_players, 10);
+        assert_eq!(motd.server_guid, Some(13253860892328930865));
+        assert_eq!(motd.gamemode.as_deref(), Some("Survival"));
+        assert_eq!(motd.port_v6, Some(19133));
+        assert_eq!(motd.to_string(), raw);
+
+        let short: BedrockMotd = "MCPE;name;390;1.17.42;0;10".parse().unwrap();
+        assert_eq!(short.level, None);
+        assert_eq!(short.to_string(), "MCPE;name;390;1.17.42;0;10;");
+
+        assert!("MCPE;name;390;1.17.42;many;10;"
+            .parse::<BedrockMotd>()
+            .is_err());
+        assert!("MCPE;name;".parse::<BedrockMotd>().is_err());
+    }
+}
@@ -109,7 +109,7 @@ impl SplitPacket {
         }
         ret
     }
-    pub fn get_frame(&mut self) -> Result<Frame> {
+    pub fn get_frame(&mut self) -> Result<Frame<'static>> {
         let buff: Vec<u8> = self.get_all();
         let mut frame = Frame::new(self.reliability.clone(), &buff);
         frame.order_index = self.order_index;
@@ -133,7 +133,7 @@ impl SplitPacketQueue {
             delete: vec![],
         }
     }
-    pub fn add(&mut self, frame: &Frame) {
+    pub fn add(&mut self, frame: &Frame<'_>) {
         if let std::collections::hash_map::Entry::Vacant(e) = self.pool.entry(frame.split_id) {
             let mut new_split = SplitPacket::new(
                 frame.split_count,
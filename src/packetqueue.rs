@@ -6,16 +6,21 @@ const NEEDS_B_AND_AS_FLAG: u8 = 0x4;
 
 const CONTINUOUS_SEND_FLAG: u8 = 0x8;
 
+const RESEND_TIMEOUT: u128 = 1000;
+
 pub struct PacketQueue {
-    pub queue: HashMap<u32, FrameSet>,
+    pub queue: HashMap<u32, FrameSet<'static>>,
     pub time_passed: HashMap<u32, (u128, bool)>,
     pub max: u32,
     send_min: u32,
     resend: Vec<u32>,
     set_size: usize,
-    set_queue: Vec<Frame>,
+    set_queue: Vec<Frame<'static>>,
     mtu: u16,
     last_tick: u128,
+    sent_at: HashMap<u32, u128>,
+    pub resent: u64,
+    pub rtt: u128,
 }
 
 impl PacketQueue {
@@ -30,9 +35,12 @@ impl PacketQueue {
             set_queue: vec![],
             mtu,
             last_tick,
+            sent_at: HashMap::new(),
+            resent: 0,
+            rtt: 0,
         }
     }
-    pub fn add_frame(&mut self, frame: Frame) {
+    pub fn add_frame(&mut self, frame: Frame<'static>) {
         if self.set_size + frame.length() < (self.mtu - 42) as usize && !frame.split {
             self.set_size += frame.length();
             self.set_queue.push(frame);
@@ -51,7 +59,7 @@ impl PacketQueue {
             self.add(set);
         }
     }
-    pub fn add(&mut self, frame_set: FrameSet) {
+    pub fn add(&mut self, frame_set: FrameSet<'static>) {
         if frame_set.sequence_number == self.max {
             self.max += 1;
             self.time_passed
@@ -59,11 +67,19 @@ impl PacketQueue {
             self.queue.insert(frame_set.sequence_number, frame_set);
         }
     }
-    pub fn received(&mut self, sequence: u32) {
+    pub fn received(&mut self, sequence: u32, time: u128) {
         if self.queue.contains_key(&sequence) {
             self.queue.remove(&sequence);
             self.time_passed.remove(&sequence);
         }
+        if let Some(sent_at) = self.sent_at.remove(&sequence) {
+            let sample = time.saturating_sub(sent_at);
+            if self.rtt == 0 {
+                self.rtt = sample;
+            } else {
+                self.rtt = (self.rtt * 7 + sample) / 8;
+            }
+        }
     }
     pub fn tick(&mut self, time: u128) {
         if !self.set_queue.is_empty() {
@@ -77,14 +93,12 @@ impl PacketQueue {
             self.set_size = 0;
         }
         let time_passed = time - self.last_tick;
-        for mut elem in self.time_passed.iter_mut() {
+        for elem in self.time_passed.iter_mut() {
             if elem.1 .1 {
                 elem.1 .0 += time_passed;
-                if elem.1 .0 > 1000 {
-                    //about 1000ms?
+                if elem.1 .0 > RESEND_TIMEOUT {
                     self.resend.push(*elem.0)
                 }
-                elem.1 .0 = time;
             }
         }
         self.last_tick = time;
@@ -97,8 +111,10 @@ impl PacketQueue {
                 self.queue.insert(self.max, added);
                 self.queue.remove(resend);
                 self.time_passed.remove(resend);
+                self.sent_at.remove(resend);
                 self.time_passed.insert(self.max, (0, false));
                 self.max += 1;
+                self.resent += 1;
             }
         }
         self.resend.clear();
@@ -110,11 +126,43 @@ impl PacketQueue {
             self.queue.insert(self.max, added);
             self.queue.remove(&index);
             self.time_passed.remove(&index);
+            self.sent_at.remove(&index);
             self.time_passed.insert(self.max, (0, false));
             self.max += 1;
+            self.resent += 1;
         }
     }
-    pub fn get_packet(&mut self, time: u128) -> Vec<&FrameSet> {
+    //sent but not acknowledged yet
+    pub fn unacknowledged_bytes(&self) -> usize {
+        self.queue
+            .iter()
+            .filter(|(sequence, _)| **sequence < self.send_min)
+            .map(|(_, set)| 4 + set.datas.iter().map(|f| f.length()).sum::<usize>())
+            .sum()
+    }
+    //waiting to be sent
+    pub fn queued_frames(&self) -> usize {
+        let unsent: usize = (self.send_min..self.max)
+            .filter_map(|sequence| self.queue.get(&sequence))
+            .map(|set| set.datas.len())
+            .sum();
+        self.set_queue.len() + unsent
+    }
+    pub fn has_unsent(&self) -> bool {
+        !self.set_queue.is_empty() || self.send_min < self.max
+    }
+    //time at which the oldest unacknowledged set will be resent
+    pub fn next_resend(&self) -> Option<u128> {
+        self.time_passed
+            .values()
+            .filter(|(_, started)| *started)
+            .map(|(passed, _)| self.last_tick + (RESEND_TIMEOUT + 1).saturating_sub(*passed))
+            .min()
+    }
+    pub fn has_pending(&self) -> bool {
+        !self.queue.is_empty() || !self.set_queue.is_empty()
+    }
+    pub fn get_packet(&mut self, time: u128) -> Vec<&FrameSet<'static>> {
         //get send able packets and start timer
         self.tick(time);
         self.readd();
@@ -122,6 +170,7 @@ impl PacketQueue {
         for i in self.send_min..self.max {
             ret.push(self.queue.get(&i).unwrap());
             self.time_passed.get_mut(&i).unwrap().1 = true;
+            self.sent_at.insert(i, time);
         }
         self.send_min = self.max;
         ret
@@ -142,4 +191,38 @@ mod packet_q_test {
         packetq.add_frame(frame);
         packetq.get_packet(time.elapsed().as_millis());
     }
+
+    #[test]
+    fn packet_q_stats() {
+        let mut packetq = PacketQueue::new(1500, 0);
+        packetq.add_frame(Frame::new(Reliability::Reliable, &[0u8; 100]));
+        packetq.add_frame(Frame::new(Reliability::Reliable, &[0u8; 100]));
+        assert_eq!(packetq.queued_frames(), 2);
+        assert_eq!(packetq.get_packet(10).len(), 1);
+        assert_eq!(packetq.queued_frames(), 0);
+        assert_eq!(packetq.unacknowledged_bytes(), 4 + 2 * (6 + 100));
+
+        packetq.resend(0);
+        assert_eq!(packetq.resent, 1);
+        assert_eq!(packetq.get_packet(20).len(), 1);
+        packetq.received(1, 50);
+        assert_eq!(packetq.rtt, 30);
+        assert_eq!(packetq.unacknowledged_bytes(), 0);
+        assert!(!packetq.has_pending());
+    }
+
+    #[test]
+    fn packet_q_resend_deadline() {
+        let mut packetq = PacketQueue::new(1500, 0);
+        assert_eq!(packetq.next_resend(), None);
+        packetq.add_frame(Frame::new(Reliability::Reliable, &[0u8; 100]));
+        assert!(packetq.has_unsent());
+        packetq.get_packet(10);
+        assert!(!packetq.has_unsent());
+        assert_eq!(packetq.next_resend(), Some(1011));
+        packetq.get_packet(500);
+        assert_eq!(packetq.next_resend(), Some(1011));
+        assert_eq!(packetq.get_packet(1011).len(), 1);
+        assert_eq!(packetq.resent, 1);
+    }
 }
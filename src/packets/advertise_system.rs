@@ -0,0 +1,43 @@
+use bytes::BufMut;
+use std::io::Result;
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
+//a server announcing itself unasked, laid out like `UnconnectedPong`
+#[derive(Clone)]
+pub struct AdvertiseSystem {
+    pub time: i64,
+    pub guid: u64,
+    _magic: bool,
+    pub data: String,
+}
+
+impl AdvertiseSystem {
+    pub fn new(time: i64, guid: u64, data: String) -> Self {
+        Self {
+            time,
+            guid,
+            _magic: true,
+            data,
+        }
+    }
+}
+
+impl Packet for AdvertiseSystem {
+    const ID: u8 = 0x1d;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Self {
+            time: cursor.i64()?,
+            guid: cursor.u64()?,
+            _magic: cursor.magic()?,
+            data: cursor.string()?.to_owned(),
+        })
+    }
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_i64(self.time);
+        buf.put_u64(self.guid);
+        buf.put_magic();
+        buf.put_string(&self.data);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct AlreadyConnected {
     _magic: bool,
@@ -15,22 +16,16 @@ impl AlreadyConnected {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for AlreadyConnected {
     const ID: u8 = 0x12;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            _magic: cursor.read_magic().await?,
-            guid: cursor.read_u64(Endian::Big).await?,
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_magic().await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u64(self.guid);
     }
 }
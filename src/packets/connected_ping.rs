@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::Decoder;
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct ConnectedPing {
     pub client_timestamp: i64,
@@ -16,21 +17,14 @@ impl ConnectedPing {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for ConnectedPing {
     const ID: u8 = 0x0;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            client_timestamp: cursor.read_i64(Endian::Big).await?,
+            client_timestamp: cursor.i64()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_i64(self.client_timestamp, Endian::Big).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_i64(self.client_timestamp);
     }
 }
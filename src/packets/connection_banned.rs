@@ -0,0 +1,31 @@
+use bytes::BufMut;
+use std::io::Result;
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
+#[derive(Clone)]
+pub struct ConnectionBanned {
+    _magic: bool,
+    pub guid: u64,
+}
+
+impl ConnectionBanned {
+    pub fn new(guid: u64) -> Self {
+        Self { _magic: true, guid }
+    }
+}
+
+impl Packet for ConnectionBanned {
+    const ID: u8 = 0x17;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Self {
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
+        })
+    }
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u64(self.guid);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::Decoder;
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct ConnectionRequest {
     pub guid: u64,
@@ -20,25 +21,18 @@ impl ConnectionRequest {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for ConnectionRequest {
     const ID: u8 = 0x9;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            guid: cursor.read_u64(Endian::Big).await?,
-            time: cursor.read_i64(Endian::Big).await?,
-            use_encryption: cursor.read_u8().await?,
+            guid: cursor.u64()?,
+            time: cursor.i64()?,
+            use_encryption: cursor.u8()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        cursor.write_i64(self.time, Endian::Big).await?;
-        cursor.write_u8(self.use_encryption).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u64(self.guid);
+        buf.put_i64(self.time);
+        buf.put_u8(self.use_encryption);
     }
 }
@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian::Big, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::{io::Result, net::SocketAddr};
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct ConnectionRequestAccepted {
     pub client_address: SocketAddr,
@@ -26,18 +27,15 @@ impl ConnectionRequestAccepted {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for ConnectionRequestAccepted {
     const ID: u8 = 0x10;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
-        let client_address = cursor.read_address().await?;
-        let system_index = cursor.read_u16(Big).await?;
-        cursor.next(((payload.len() - 16) - cursor.pos() as usize) as u64);
-        let request_timestamp = cursor.read_i64(Big).await?;
-        let accepted_timestamp = cursor.read_i64(Big).await?;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        let client_address = cursor.address()?;
+        let system_index = cursor.u16()?;
+        //system addresses, only the timestamps at the end are used
+        cursor.skip(cursor.remaining().saturating_sub(16))?;
+        let request_timestamp = cursor.i64()?;
+        let accepted_timestamp = cursor.i64()?;
         Ok(Self {
             client_address,
             system_index,
@@ -45,13 +43,11 @@ impl Packet for ConnectionRequestAccepted {
             accepted_timestamp,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_address(self.client_address).await?;
-        cursor.write_u16(self.system_index, Big).await?;
-        cursor.write(&[0x6u8; 10]).await?;
-        cursor.write_i64(self.request_timestamp, Big).await?;
-        cursor.write_i64(self.accepted_timestamp, Big).await?;
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_address(self.client_address);
+        buf.put_u16(self.system_index);
+        buf.put_slice(&[0x6u8; 10]);
+        buf.put_i64(self.request_timestamp);
+        buf.put_i64(self.accepted_timestamp);
     }
 }
@@ -1,18 +1,16 @@
-use crate::packets::Packet;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::Decoder;
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct Disconnected;
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for Disconnected {
     const ID: u8 = 0x15;
-    async fn read(_payload: &[u8]) -> Result<Self> {
+    fn read(_cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self)
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        Ok(vec![])
-    }
+    fn write<B: BufMut>(&self, _buf: &mut B) {}
 }
@@ -1,18 +1,14 @@
-use std::io::Result;
+use bytes::BufMut;
+use std::{borrow::Cow, io::Result};
 
-use crate::{
-    reader::{
-        Endian::{self, Big, Little},
-        Reader,
-    },
-    writer::Writer,
-};
+use crate::codec::{BufMutExt, Decoder};
 
 use super::Reliability;
 const SPLIT_FLAG: u8 = 0x10;
 
+//decoded frames borrow their body from the datagram, frames that are sent own it
 #[derive(Clone)]
-pub struct Frame {
+pub struct Frame<'a> {
     pub reliability: Reliability,
 
     pub message_index: u32,
@@ -23,10 +19,10 @@ pub struct Frame {
     pub split_count: u32,
     pub split_index: u32,
     pub split_id: u16,
-    pub data: Vec<u8>,
+    pub data: Cow<'a, [u8]>,
 }
 
-impl Frame {
+impl Frame<'static> {
     pub fn new(reliability: Reliability, data: &[u8]) -> Self {
         Self {
             reliability,
@@ -37,7 +33,17 @@ impl Frame {
             split_count: 0,
             split_index: 0,
             split_id: 0,
-            data: data.to_vec(),
+            data: Cow::Owned(data.to_vec()),
+        }
+    }
+}
+
+impl<'a> Frame<'a> {
+    //copies the body out of the datagram, for frames that have to wait
+    pub fn into_owned(self) -> Frame<'static> {
+        Frame {
+            data: Cow::Owned(self.data.into_owned()),
+            ..self
         }
     }
     pub fn length(&self) -> usize {
@@ -59,7 +65,7 @@ impl Frame {
         ret += self.data.len();
         ret
     }
-    pub async fn decode(cursor: &mut Reader<'_>) -> Result<Self> {
+    pub fn decode(cursor: &mut Decoder<'a>) -> Result<Self> {
         let mut packet = Self {
             reliability: Reliability::new(0)?,
 
@@ -71,61 +77,59 @@ impl Frame {
             split_count: 0,
             split_index: 0,
             split_id: 0,
-            data: vec![],
+            data: Cow::Borrowed(&[]),
         };
 
-        let header = cursor.read_u8().await?;
+        let header = cursor.u8()?;
         packet.split = (header & SPLIT_FLAG) != 0;
         packet.reliability = Reliability::new((header & 224) >> 5)?;
-        let mut packet_length = cursor.read_u16(Endian::Big).await?;
+        let mut packet_length = cursor.u16()?;
         packet_length >>= 3;
 
         if packet.reliability.reliable() {
-            packet.message_index = cursor.read_u24(Endian::Little).await?;
+            packet.message_index = cursor.u24_le()?;
         }
 
         if packet.reliability.sequenced() {
-            packet.sequence_index = cursor.read_u24(Endian::Little).await?;
+            packet.sequence_index = cursor.u24_le()?;
         }
 
         if packet.reliability.sequenced_or_ordered() {
-            packet.order_index = cursor.read_u24(Endian::Little).await?;
-            cursor.next(1);
+            packet.order_index = cursor.u24_le()?;
+            cursor.skip(1)?;
         }
 
         if packet.split {
-            packet.split_count = cursor.read_u32(Endian::Big).await?;
-            packet.split_id = cursor.read_u16(Endian::Big).await?;
-            packet.split_index = cursor.read_u32(Endian::Big).await?;
+            packet.split_count = cursor.u32()?;
+            packet.split_id = cursor.u16()?;
+            packet.split_index = cursor.u32()?;
         }
-        packet.data = vec![0u8; packet_length as usize];
-        cursor.read(&mut packet.data).await?;
+        packet.data = Cow::Borrowed(cursor.bytes(packet_length as usize)?);
         Ok(packet)
     }
 
-    pub async fn encode(&self, cursor: &mut Writer) -> Result<()> {
+    pub fn encode_into<B: BufMut>(&self, buf: &mut B) {
         let mut header = self.reliability.to_byte() << 5;
         if self.split {
             header |= SPLIT_FLAG;
         }
-        cursor.write_u8(header).await?;
-        cursor.write_u16((self.data.len() * 8) as u16, Big).await?;
+        buf.put_u8(header);
+        buf.put_u16((self.data.len() * 8) as u16);
         if self.reliability.reliable() {
-            cursor.write_u24(self.message_index, Endian::Little).await?;
+            buf.put_u24_le(self.message_index);
         }
         if self.reliability.sequenced() {
-            cursor.write_u24(self.sequence_index, Little).await?;
+            buf.put_u24_le(self.sequence_index);
         }
         if self.reliability.sequenced_or_ordered() {
-            cursor.write_u24(self.order_index, Endian::Little).await?;
-            cursor.write_u8(0).await?;
+            buf.put_u24_le(self.order_index);
+            buf.put_u8(0);
         }
         if self.split {
-            cursor.write_u32(self.split_count, Endian::Big).await?;
-            cursor.write_u16(self.split_id, Endian::Big).await?;
-            cursor.write_u32(self.split_index, Endian::Big).await?;
+            buf.put_u32(self.split_count);
+            buf.put_u16(self.split_id);
+            buf.put_u32(self.split_index);
         }
-        cursor.write(&self.data).await?;
-        Ok(())
+        buf.put_slice(&self.data);
     }
 }
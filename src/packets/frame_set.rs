@@ -1,40 +1,43 @@
+use bytes::BufMut;
 use std::io::Result;
 
 use super::frame::Frame;
-use crate::{
-    reader::{Endian::Little, Reader},
-    writer::Writer,
-};
+use crate::codec::{BufMutExt, Decoder};
 
 #[derive(Clone)]
-pub struct FrameSet {
+pub struct FrameSet<'a> {
     pub header: u8,
     pub sequence_number: u32,
-    pub datas: Vec<Frame>,
+    pub datas: Vec<Frame<'a>>,
 }
 
-impl FrameSet {
-    pub async fn decode(payload: &[u8]) -> Result<Self> {
-        let size = payload.len();
-        let mut cursor = Reader::new(payload);
+impl<'a> FrameSet<'a> {
+    pub fn decode(payload: &'a [u8]) -> Result<Self> {
+        let mut cursor = Decoder::new(payload);
         let mut frame_set = Self {
-            header: cursor.read_u8().await?,
-            sequence_number: cursor.read_u24(Little).await?,
+            header: cursor.u8()?,
+            sequence_number: cursor.u24_le()?,
             datas: vec![],
         };
-        while cursor.pos() < size as u64 {
-            frame_set.datas.push(Frame::decode(&mut cursor).await?)
+        while cursor.remaining() > 0 {
+            frame_set.datas.push(Frame::decode(&mut cursor)?)
         }
 
         Ok(frame_set)
     }
-    pub async fn encode(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_u8(self.header).await?;
-        cursor.write_u24(self.sequence_number, Little).await?;
+    pub fn length(&self) -> usize {
+        4 + self.datas.iter().map(|frame| frame.length()).sum::<usize>()
+    }
+    pub fn encode_into<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(self.header);
+        buf.put_u24_le(self.sequence_number);
         for frame in &self.datas {
-            frame.encode(&mut cursor).await?;
+            frame.encode_into(buf);
         }
-        Ok(cursor.get_raw_payload())
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.length());
+        self.encode_into(&mut buf);
+        buf
     }
 }
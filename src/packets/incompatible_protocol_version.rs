@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct IncompatibleProtocolVersion {
     pub server_protocol: u8,
@@ -20,25 +21,18 @@ impl IncompatibleProtocolVersion {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for IncompatibleProtocolVersion {
     const ID: u8 = 0x19;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            server_protocol: cursor.read_u8().await?,
-            _magic: cursor.read_magic().await?,
-            server_guid: cursor.read_u64(Endian::Big).await?,
+            server_protocol: cursor.u8()?,
+            _magic: cursor.magic()?,
+            server_guid: cursor.u64()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_u8(self.server_protocol).await?;
-        cursor.write_magic().await?;
-        cursor.write_u64(self.server_guid, Endian::Big).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(self.server_protocol);
+        buf.put_magic();
+        buf.put_u64(self.server_guid);
     }
 }
@@ -1,7 +1,9 @@
 pub(crate) mod ack;
+pub(crate) mod advertise_system;
 pub(crate) mod already_connected;
 pub(crate) mod connected_ping;
 pub(crate) mod connected_pong;
+pub(crate) mod connection_banned;
 pub(crate) mod connection_request;
 pub(crate) mod connection_request_accepted;
 pub(crate) mod disconnected;
@@ -10,17 +12,22 @@ pub(crate) mod frame_set;
 pub(crate) mod incompatible_protocol_version;
 pub(crate) mod nack;
 pub(crate) mod new_incoming_connection;
+pub(crate) mod no_free_incoming_connections;
 pub(crate) mod open_connection_reply1;
 pub(crate) mod open_connection_reply2;
 pub(crate) mod open_connection_request1;
 pub(crate) mod open_connection_request2;
+pub(crate) mod out_of_band;
 pub(crate) mod unconnected_ping;
+pub(crate) mod unconnected_ping_open_connections;
 pub(crate) mod unconnected_pong;
 
 pub use ack::*;
+pub use advertise_system::*;
 pub use already_connected::*;
 pub use connected_ping::*;
 pub use connected_pong::*;
+pub use connection_banned::*;
 pub use connection_request::*;
 pub use connection_request_accepted::*;
 pub use disconnected::*;
@@ -29,14 +36,17 @@ pub use frame_set::*;
 pub use incompatible_protocol_version::*;
 pub use nack::*;
 pub use new_incoming_connection::*;
+pub use no_free_incoming_connections::*;
 pub use open_connection_reply1::*;
 pub use open_connection_reply2::*;
 pub use open_connection_request1::*;
 pub use open_connection_request2::*;
+pub use out_of_band::*;
 pub use unconnected_ping::*;
+pub use unconnected_ping_open_connections::*;
 pub use unconnected_pong::*;
 
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 #[derive(Clone)]
 pub enum Reliability {
     Unreliable,
@@ -54,10 +64,7 @@ impl Reliability {
             0x2 => Ok(Self::Reliable),
             0x3 => Ok(Self::ReliableOrdered),
             0x4 => Ok(Self::ReliableSequenced),
-            _ => Err(Error::new(
-                ErrorKind::Other,
-                format!("unknown reliability byte {}", &byte),
-            )),
+            _ => Err(Error::other(format!("unknown reliability byte {}", &byte))),
         }
     }
     pub(crate) fn to_byte(&self) -> u8 {
@@ -98,24 +105,31 @@ pub const MAGIC: [u8; 16] = [
     0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
 ];
 
-use async_trait::async_trait;
+use bytes::BufMut;
 use std::io::Result;
 
-#[async_trait]
+use crate::codec::Decoder;
+
 pub trait Packet: Clone {
     const ID: u8;
-    async fn read(buf: &[u8]) -> Result<Self>
-    where
-        Self: Sized;
-    async fn write(&self) -> Result<Vec<u8>>;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self>;
+    fn write<B: BufMut>(&self, buf: &mut B);
+    fn encode_into<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(Self::ID);
+        self.write(buf);
+    }
 }
 
-pub async fn encode<T: Packet>(packet: T) -> Result<Vec<u8>> {
-    Ok([&[T::ID], &*packet.write().await?].concat())
+pub fn encode<T: Packet>(packet: T) -> Vec<u8> {
+    let mut buf = vec![];
+    packet.encode_into(&mut buf);
+    buf
 }
 
-pub async fn decode<T: Packet>(buf: &[u8]) -> Result<T> {
-    T::read(&buf[1..]).await
+pub fn decode<T: Packet>(buf: &[u8]) -> Result<T> {
+    let mut cursor = Decoder::new(buf);
+    cursor.skip(1)?;
+    T::read(&mut cursor)
 }
 
 #[test]
@@ -1,10 +1,8 @@
+use bytes::BufMut;
 use std::io::Result;
 
-use crate::{
-    packets::Packet,
-    reader::{Endian, Reader},
-    writer::Writer,
-};
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
 
 #[derive(Clone)]
 pub struct Nack {
@@ -33,33 +31,26 @@ impl Nack {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for Nack {
     const ID: u8 = 0xa0;
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_u16(self.record_count, Endian::Big).await?;
-        cursor.write_u8(self.max_equals_min as u8).await?;
-        cursor.write_u24(self.sequences.0, Endian::Little).await?;
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u16(self.record_count);
+        buf.put_u8(self.max_equals_min as u8);
+        buf.put_u24_le(self.sequences.0);
         if !self.max_equals_min {
-            cursor.write_u24(self.sequences.1, Endian::Little).await?;
+            buf.put_u24_le(self.sequences.1);
         }
-        Ok(cursor.get_raw_payload())
     }
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
-        let record_count = cursor.read_u16(Endian::Big).await?;
-        let max_equals_min = cursor.read_u8().await? != 0;
-        let sequences;
-        let sequence = cursor.read_u24(Endian::Little).await?;
-        if max_equals_min {
-            sequences = (sequence, sequence)
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        let record_count = cursor.u16()?;
+        let max_equals_min = cursor.u8()? != 0;
+        let sequence = cursor.u24_le()?;
+        let sequences = if max_equals_min {
+            (sequence, sequence)
         } else {
-            let sequence_max = cursor.read_u24(Endian::Little).await?;
-            sequences = (sequence, sequence_max)
-        }
+            let sequence_max = cursor.u24_le()?;
+            (sequence, sequence_max)
+        };
         Ok(Self {
             record_count,
             max_equals_min,
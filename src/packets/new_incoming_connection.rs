@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::{io::Result, net::SocketAddr};
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct NewIncomingConnection {
     pub server_address: SocketAddr,
@@ -10,34 +11,22 @@ pub struct NewIncomingConnection {
     pub accepted_timestamp: i64,
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for NewIncomingConnection {
     const ID: u8 = 0x13;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            server_address: cursor.read_address().await?,
+            server_address: cursor.address()?,
             request_timestamp: {
-                cursor.next(((payload.len() - 16) - cursor.pos() as usize) as u64);
-                cursor.read_i64(Endian::Big).await?
+                cursor.skip(cursor.remaining().saturating_sub(16))?;
+                cursor.i64()?
             },
-            accepted_timestamp: cursor.read_i64(Endian::Big).await?,
+            accepted_timestamp: cursor.i64()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_address(self.server_address).await?;
-        for _ in 0..10 {
-            cursor.write_u8(0x06).await?;
-        }
-        cursor
-            .write_i64(self.request_timestamp, Endian::Big)
-            .await?;
-        cursor
-            .write_i64(self.accepted_timestamp, Endian::Big)
-            .await?;
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_address(self.server_address);
+        buf.put_slice(&[0x6u8; 10]);
+        buf.put_i64(self.request_timestamp);
+        buf.put_i64(self.accepted_timestamp);
     }
 }
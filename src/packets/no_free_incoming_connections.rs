@@ -0,0 +1,32 @@
+use bytes::BufMut;
+use std::io::Result;
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
+//the server has no slot left for another connection
+#[derive(Clone)]
+pub struct NoFreeIncomingConnections {
+    _magic: bool,
+    pub guid: u64,
+}
+
+impl NoFreeIncomingConnections {
+    pub fn new(guid: u64) -> Self {
+        Self { _magic: true, guid }
+    }
+}
+
+impl Packet for NoFreeIncomingConnections {
+    const ID: u8 = 0x14;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Self {
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
+        })
+    }
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u64(self.guid);
+    }
+}
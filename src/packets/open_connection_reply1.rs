@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct OpenConnectionReply1 {
     _magic: bool,
@@ -23,27 +24,20 @@ impl OpenConnectionReply1 {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for OpenConnectionReply1 {
     const ID: u8 = 0x6;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            _magic: cursor.read_magic().await?,
-            guid: cursor.read_u64(Endian::Big).await?,
-            use_encryption: cursor.read_u8().await?,
-            mtu_size: cursor.read_u16(Endian::Big).await?,
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
+            use_encryption: cursor.u8()?,
+            mtu_size: cursor.u16()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_magic().await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        cursor.write_u8(self.use_encryption).await?;
-        cursor.write_u16(self.mtu_size, Endian::Big).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u64(self.guid);
+        buf.put_u8(self.use_encryption);
+        buf.put_u16(self.mtu_size);
     }
 }
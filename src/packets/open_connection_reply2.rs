@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::{io::Result, net::SocketAddr};
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct OpenConnectionReply2 {
     _magic: bool,
@@ -24,29 +25,22 @@ impl OpenConnectionReply2 {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for OpenConnectionReply2 {
     const ID: u8 = 0x8;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            _magic: cursor.read_magic().await?,
-            guid: cursor.read_u64(Endian::Big).await?,
-            address: cursor.read_address().await?,
-            mtu: cursor.read_u16(Endian::Big).await?,
-            encryption_enabled: cursor.read_u8().await?,
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
+            address: cursor.address()?,
+            mtu: cursor.u16()?,
+            encryption_enabled: cursor.u8()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_magic().await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        cursor.write_address(self.address).await?;
-        cursor.write_u16(self.mtu, Endian::Big).await?;
-        cursor.write_u8(self.encryption_enabled).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u64(self.guid);
+        buf.put_address(self.address);
+        buf.put_u16(self.mtu);
+        buf.put_u8(self.encryption_enabled);
     }
 }
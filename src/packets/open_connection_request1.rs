@@ -1,7 +1,11 @@
-use crate::packets::Packet;
-use crate::reader::Reader;
-use crate::writer::Writer;
-use std::{convert::TryInto, io::Result};
+use bytes::BufMut;
+use std::{
+    convert::TryInto,
+    io::{Error, ErrorKind, Result},
+};
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::{Packet, MAGIC};
 
 #[derive(Clone)]
 pub struct OpenConnectionRequest1 {
@@ -19,27 +23,26 @@ impl OpenConnectionRequest1 {
         }
     }
 }
-use async_trait::async_trait;
 
-#[async_trait]
 impl Packet for OpenConnectionRequest1 {
     const ID: u8 = 0x5;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        //the request is padded to the mtu, id and udp/ip headers make up the rest
+        let mtu_size = (cursor.remaining() + 29)
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "mtu too large"))?;
         Ok(Self {
-            _magic: cursor.read_magic().await?,
-            protocol_version: cursor.read_u8().await?,
-            mtu_size: (payload.len() + 29).try_into().unwrap(),
+            _magic: cursor.magic()?,
+            protocol_version: cursor.u8()?,
+            mtu_size,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_magic().await?;
-        cursor.write_u8(self.protocol_version).await?;
-        cursor
-            .write(vec![0; (self.mtu_size as usize) - (cursor.pos() as usize + 28) - 1].as_slice())
-            .await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_u8(self.protocol_version);
+        buf.put_bytes(
+            0,
+            (self.mtu_size as usize).saturating_sub(MAGIC.len() + 1 + 29),
+        );
     }
 }
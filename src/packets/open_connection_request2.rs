@@ -1,7 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::{io::Result, net::SocketAddr};
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct OpenConnectionRequest2 {
     _magic: bool,
@@ -21,28 +23,20 @@ impl OpenConnectionRequest2 {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for OpenConnectionRequest2 {
     const ID: u8 = 0x7;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            _magic: cursor.read_magic().await?,
-            address: cursor.read_address().await?,
-            mtu: cursor.read_u16(Endian::Big).await?,
-            guid: cursor.read_u64(Endian::Big).await?,
+            _magic: cursor.magic()?,
+            address: cursor.address()?,
+            mtu: cursor.u16()?,
+            guid: cursor.u64()?,
         })
     }
-
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_magic().await?;
-        cursor.write_address(self.address).await?;
-        cursor.write_u16(self.mtu, Endian::Big).await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_magic();
+        buf.put_address(self.address);
+        buf.put_u16(self.mtu);
+        buf.put_u64(self.guid);
     }
 }
@@ -0,0 +1,57 @@
+use bytes::BufMut;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
+//RakNet peers drop longer offline data
+pub const MAX_OUT_OF_BAND: usize = 400;
+
+//application data sent without a connection, RakNet's ID_OUT_OF_BAND_INTERNAL
+#[derive(Clone)]
+pub struct OutOfBand {
+    pub guid: u64,
+    _magic: bool,
+    pub data: Vec<u8>,
+}
+
+impl OutOfBand {
+    pub fn new(guid: u64, data: Vec<u8>) -> Self {
+        Self {
+            guid,
+            _magic: true,
+            data,
+        }
+    }
+}
+
+impl Packet for OutOfBand {
+    const ID: u8 = 0x0d;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        let guid = cursor.u64()?;
+        //the id alone is too weak to tell stray datagrams apart
+        if !cursor.magic()? {
+            return Err(Error::new(ErrorKind::InvalidData, "missing offline magic"));
+        }
+        Ok(Self {
+            guid,
+            _magic: true,
+            data: cursor.rest().to_vec(),
+        })
+    }
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u64(self.guid);
+        buf.put_magic();
+        buf.put_slice(&self.data);
+    }
+}
+
+pub(crate) fn encode_out_of_band(guid: u64, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() > MAX_OUT_OF_BAND {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "offline message is longer than MAX_OUT_OF_BAND",
+        ));
+    }
+    Ok(super::encode(OutOfBand::new(guid, data.to_vec())))
+}
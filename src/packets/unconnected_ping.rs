@@ -1,8 +1,9 @@
-use crate::packets::Packet;
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
 #[derive(Clone)]
 pub struct UnconnectedPing {
     pub time: i64,
@@ -20,24 +21,18 @@ impl UnconnectedPing {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for UnconnectedPing {
     const ID: u8 = 0x01;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            time: cursor.read_i64(Endian::Big).await?,
-            _magic: cursor.read_magic().await?,
-            guid: cursor.read_u64(Endian::Big).await?,
+            time: cursor.i64()?,
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_i64(self.time, Endian::Big).await?;
-        cursor.write_magic().await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_i64(self.time);
+        buf.put_magic();
+        buf.put_u64(self.guid);
     }
 }
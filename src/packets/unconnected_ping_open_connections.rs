@@ -0,0 +1,39 @@
+use bytes::BufMut;
+use std::io::Result;
+
+use crate::codec::{BufMutExt, Decoder};
+use crate::packets::Packet;
+
+//only answered by servers with free slots
+#[derive(Clone)]
+pub struct UnconnectedPingOpenConnections {
+    pub time: i64,
+    _magic: bool,
+    pub guid: u64,
+}
+
+impl UnconnectedPingOpenConnections {
+    pub fn new(time: i64, guid: u64) -> Self {
+        Self {
+            time,
+            _magic: true,
+            guid,
+        }
+    }
+}
+
+impl Packet for UnconnectedPingOpenConnections {
+    const ID: u8 = 0x02;
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
+        Ok(Self {
+            time: cursor.i64()?,
+            _magic: cursor.magic()?,
+            guid: cursor.u64()?,
+        })
+    }
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_i64(self.time);
+        buf.put_magic();
+        buf.put_u64(self.guid);
+    }
+}
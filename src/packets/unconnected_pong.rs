@@ -1,7 +1,7 @@
-use crate::reader::{Endian, Reader};
-use crate::writer::Writer;
+use bytes::BufMut;
 use std::io::Result;
 
+use crate::codec::{BufMutExt, Decoder};
 use crate::packets::Packet;
 
 #[derive(Clone)]
@@ -23,26 +23,20 @@ impl UnconnectedPong {
     }
 }
 
-use async_trait::async_trait;
-
-#[async_trait]
 impl Packet for UnconnectedPong {
     const ID: u8 = 0x1c;
-    async fn read(payload: &[u8]) -> Result<Self> {
-        let mut cursor = Reader::new(payload);
+    fn read(cursor: &mut Decoder<'_>) -> Result<Self> {
         Ok(Self {
-            time: cursor.read_i64(Endian::Big).await?,
-            guid: cursor.read_u64(Endian::Big).await?,
-            _magic: cursor.read_magic().await?,
-            motd: cursor.read_string().await?.to_owned(),
+            time: cursor.i64()?,
+            guid: cursor.u64()?,
+            _magic: cursor.magic()?,
+            motd: cursor.string()?.to_owned(),
         })
     }
-    async fn write(&self) -> Result<Vec<u8>> {
-        let mut cursor = Writer::new(vec![]);
-        cursor.write_i64(self.time, Endian::Big).await?;
-        cursor.write_u64(self.guid, Endian::Big).await?;
-        cursor.write_magic().await?;
-        cursor.write_string(&self.motd).await?;
-        Ok(cursor.get_raw_payload())
+    fn write<B: BufMut>(&self, buf: &mut B) {
+        buf.put_i64(self.time);
+        buf.put_u64(self.guid);
+        buf.put_magic();
+        buf.put_string(&self.motd);
     }
 }
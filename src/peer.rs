@@ -0,0 +1,107 @@
+use std::{io::Result, net::SocketAddr, sync::Arc};
+
+use crate::{
+    driver::{wait_pong, ConnectionHandle},
+    eventqueue::EventReceiver,
+    DisconnectReason, Latency, RaknetEvent, RaknetStatistics, RemoteClock,
+};
+
+pub struct Peer {
+    connection: Arc<ConnectionHandle>,
+    receiver: EventReceiver,
+    address: SocketAddr,
+    guid: u64,
+    closed: bool,
+}
+
+impl Peer {
+    pub(crate) fn new(
+        connection: Arc<ConnectionHandle>,
+        receiver: EventReceiver,
+        address: SocketAddr,
+        guid: u64,
+    ) -> Self {
+        Self {
+            connection,
+            receiver,
+            address,
+            guid,
+            closed: false,
+        }
+    }
+
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn guid(&self) -> u64 {
+        self.guid
+    }
+
+    pub async fn send(&self, buff: &[u8]) -> Result<()> {
+        if self.closed {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "Not connected",
+            ));
+        }
+        self.connection.send(buff);
+        Ok(())
+    }
+
+    /// Waits for the next event of this peer.
+    /// Returns `None` once the `Disconnected` event has been delivered.
+    pub async fn recv(&mut self) -> Option<RaknetEvent> {
+        if self.closed {
+            return None;
+        }
+        let event = self.receiver.recv().await;
+        if let Some(RaknetEvent::Disconnected(..)) | None = event {
+            self.closed = true;
+        }
+        event
+    }
+
+    pub(crate) fn try_recv(&mut self) -> Option<RaknetEvent> {
+        if self.closed {
+            return None;
+        }
+        let event = self.receiver.try_recv();
+        if let Some(RaknetEvent::Disconnected(..)) = event {
+            self.closed = true;
+        }
+        event
+    }
+
+    //the `Disconnected` event is still delivered by `recv`
+    pub async fn close(&self) {
+        self.connection.close(DisconnectReason::Closed);
+    }
+
+    pub async fn stats(&self) -> RaknetStatistics {
+        self.connection.stats()
+    }
+
+    pub async fn latency(&self) -> Option<Latency> {
+        self.connection.latency()
+    }
+
+    //offset to the remote clock, converts timestamps between both sides
+    pub async fn remote_clock(&self) -> Option<RemoteClock> {
+        self.connection.remote_clock()
+    }
+
+    //sends a connected ping and waits for the round trip time in milliseconds
+    pub async fn ping(&self) -> Result<u128> {
+        wait_pong(self.connection.ping()).await
+    }
+
+    //events discarded because the queue was full
+    pub fn dropped_events(&self) -> u64 {
+        self.receiver.dropped()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
@@ -1,39 +1,222 @@
-use std::{io::Result, net::SocketAddr};
+use rand::random;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    sync::atomic::{AtomicI64, Ordering},
+    time::{Duration, Instant},
+};
 
-use tokio::net::UdpSocket;
+use tokio::{net::UdpSocket, sync::Mutex};
 
-use crate::packets::*;
+use crate::{
+    macros::*,
+    packets::*,
+    time,
+    transport::{bind_dual_stack, normalise, outbound, Transport},
+    BedrockMotd,
+};
 
-pub struct Ping {
-    socket: UdpSocket,
+//a server that answered a ping
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pong {
+    pub address: SocketAddr,
+    pub guid: u64,
+    pub rtt: Duration,
+    pub motd: String,
 }
 
-impl Ping {
-    pub async fn new() -> Self {
+impl Pong {
+    pub fn bedrock_motd(&self) -> Result<BedrockMotd> {
+        self.motd.parse()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PingConfig {
+    //how long each attempt waits for the pong
+    pub timeout: Duration,
+    //attempts after the first one, earlier attempts are still answered meanwhile
+    pub retries: u32,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
         Self {
-            socket: UdpSocket::bind("0.0.0.0:0")
-                .await
-                .expect("Unable to bind to address"),
+            timeout: Duration::from_secs(1),
+            retries: 2,
         }
     }
-    pub async fn ping(&self, address: SocketAddr) -> Result<String> {
-        let unconnected_ping = UnconnectedPing::new(0, 0);
-        let payload = encode(unconnected_ping).await?;
-        let mut ret = String::new();
-        self.socket.send_to(&payload, address).await?;
+}
+
+/// Pings servers over one socket.
+///
+/// Every ping carries its own nonce in the timestamp field, which servers echo
+/// in the pong, so replies are matched to their request. Calls on one `Ping`
+/// run one after another, `ping_many` pings several servers at once.
+pub struct Ping<T = UdpSocket> {
+    socket: T,
+    guid: u64,
+    config: PingConfig,
+    next_nonce: AtomicI64,
+    receiving: Mutex<()>,
+}
+
+impl Ping<UdpSocket> {
+    //reaches IPv6 and IPv4 servers, or only IPv4 ones where IPv6 is unavailable
+    pub async fn new() -> Result<Self> {
+        let socket = match bind_dual_stack("[::]:0".parse().unwrap()).await {
+            Ok(socket) => socket,
+            Err(_) => UdpSocket::bind("0.0.0.0:0").await?,
+        };
+        Self::with_socket(socket)
+    }
+
+    //pings from `local`, e.g. one interface of a multi-homed host
+    pub async fn bind(local: SocketAddr) -> Result<Self> {
+        Self::with_socket(UdpSocket::bind(local).await?)
+    }
+
+    fn with_socket(socket: UdpSocket) -> Result<Self> {
+        //lets `discover` reach broadcast addresses
+        socket.set_broadcast(true)?;
+        Ok(Self::with_transport(socket))
+    }
+}
+
+impl<T: Transport> Ping<T> {
+    //pings over `socket` instead of a UDP socket of its own
+    pub fn with_transport(socket: T) -> Self {
+        Self {
+            socket,
+            guid: random::<u64>() | 1,
+            config: PingConfig::default(),
+            next_nonce: AtomicI64::new((time() as i64).max(1)),
+            receiving: Mutex::new(()),
+        }
+    }
+
+    pub fn set_config(&mut self, config: PingConfig) {
+        self.config = config;
+    }
+
+    /// Pings `address` until its pong arrives or the attempts run out,
+    /// replies from other addresses or to other pings are skipped.
+    pub async fn ping(&self, address: SocketAddr) -> Result<Pong> {
+        self.ping_many(&[address]).await.remove(0)
+    }
+
+    /// Pings all `addresses` at once over the one socket,
+    /// the results are in the order of `addresses`.
+    pub async fn ping_many(&self, addresses: &[SocketAddr]) -> Vec<Result<Pong>> {
+        let _receiving = self.receiving.lock().await;
+        let addresses: Vec<SocketAddr> = addresses.iter().map(|a| normalise(*a)).collect();
+        let mut results: Vec<Option<Result<Pong>>> = addresses.iter().map(|_| None).collect();
+        //nonce -> index of the address and when the ping left
+        let mut pending: HashMap<i64, (usize, Instant)> = HashMap::new();
         let mut v = [0u8; 1500];
+        'attempts: for _ in 0..=self.config.retries {
+            for (index, address) in addresses.iter().enumerate() {
+                if results[index].is_some() {
+                    continue;
+                }
+                match self.send_ping(*address).await {
+                    Ok((nonce, sent)) => {
+                        pending.insert(nonce, (index, sent));
+                    }
+                    Err(e) => results[index] = Some(Err(e)),
+                }
+            }
+            let deadline = tokio::time::Instant::now() + self.config.timeout;
+            while results.iter().any(|result| result.is_none()) {
+                let received =
+                    match tokio::time::timeout_at(deadline, self.socket.recv_from(&mut v)).await {
+                        Ok(received) => received,
+                        Err(_) => continue 'attempts,
+                    };
+                let (size, source) = match received {
+                    Ok(p) => p,
+                    Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                    Err(e) => {
+                        for result in results.iter_mut().filter(|result| result.is_none()) {
+                            *result = Some(Err(Error::new(e.kind(), e.to_string())));
+                        }
+                        break 'attempts;
+                    }
+                };
+                let pong = match self.pong(&v[..size], source, &pending) {
+                    Some(pong) => pong,
+                    None => continue,
+                };
+                let (index, _) = pending[&pong.0];
+                if pong.1.address == addresses[index] && results[index].is_none() {
+                    results[index] = Some(Ok(pong.1));
+                }
+            }
+            break;
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| Err(Error::new(ErrorKind::TimedOut, "No pong received")))
+            })
+            .collect()
+    }
 
-        let timeout = tokio::time::timeout(
-            std::time::Duration::from_secs(1),
-            self.socket.recv_from(&mut v),
-        );
-        let (size, _source) = timeout.await??;
-        let buff = &v[..size];
-        if buff[0] == UnconnectedPong::ID {
-            let pong = decode::<UnconnectedPong>(buff).await?;
-            ret = pong.motd;
+    /// Pings every server behind `target`, a broadcast address like
+    /// `255.255.255.255:19132` or an IPv6 multicast group like `[ff02::1%2]:19132`,
+    /// and collects the pongs that arrive within `window`, one per server.
+    pub async fn discover(&self, target: SocketAddr, window: Duration) -> Result<Vec<Pong>> {
+        let _receiving = self.receiving.lock().await;
+        let (nonce, sent) = self.send_ping(target).await?;
+        let pending = HashMap::from([(nonce, (0, sent))]);
+        let deadline = tokio::time::Instant::from_std(sent + window);
+        let mut seen = HashSet::new();
+        let mut pongs = vec![];
+        let mut v = [0u8; 1500];
+        while let Ok(received) =
+            tokio::time::timeout_at(deadline, self.socket.recv_from(&mut v)).await
+        {
+            //an ICMP error of one pinged host ends nothing
+            let (size, source) = unwrap_or_continue!(received);
+            if let Some((_, pong)) = self.pong(&v[..size], source, &pending) {
+                if seen.insert((pong.address, pong.guid)) {
+                    pongs.push(pong);
+                }
+            }
         }
+        Ok(pongs)
+    }
 
-        Ok(ret)
+    async fn send_ping(&self, target: SocketAddr) -> Result<(i64, Instant)> {
+        let nonce = self.next_nonce.fetch_add(1, Ordering::Relaxed);
+        let payload = encode(UnconnectedPing::new(nonce, self.guid));
+        let target = outbound(target, &self.socket.local_addr()?);
+        let sent = Instant::now();
+        self.socket.send_to(&payload, target).await?;
+        Ok((nonce, sent))
+    }
+
+    //a pong to one of the `pending` pings with its nonce
+    fn pong(
+        &self,
+        buff: &[u8],
+        source: SocketAddr,
+        pending: &HashMap<i64, (usize, Instant)>,
+    ) -> Option<(i64, Pong)> {
+        if buff.is_empty() || buff[0] != UnconnectedPong::ID {
+            return None;
+        }
+        let pong = decode::<UnconnectedPong>(buff).ok()?;
+        let (_, sent) = pending.get(&pong.time)?;
+        Some((
+            pong.time,
+            Pong {
+                address: normalise(source),
+                guid: pong.guid,
+                rtt: sent.elapsed(),
+                motd: pong.motd,
+            },
+        ))
     }
 }
@@ -0,0 +1,50 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::BedrockMotd;
+
+//what a pong provider knows about the ping it answers
+#[derive(Clone, Debug)]
+pub struct PingInfo {
+    pub source: SocketAddr,
+    pub client_guid: u64,
+    //connections that completed the handshake
+    pub connections: usize,
+    //set with `Server::set_motd`
+    pub motd: String,
+}
+
+pub(crate) type PongProvider = Arc<dyn Fn(&PingInfo) -> String + Send + Sync>;
+
+/// A pong provider for Bedrock servers, see `Server::set_pong_provider`.
+/// Answers with the motd whose player count is the current number of connections,
+/// motds that are not Bedrock ones pass unchanged.
+pub fn bedrock_players(ping: &PingInfo) -> String {
+    match ping.motd.parse::<BedrockMotd>() {
+        Ok(mut motd) => {
+            motd.players = ping.connections as u32;
+            motd.to_string()
+        }
+        Err(_) => ping.motd.clone(),
+    }
+}
+
+#[cfg(test)]
+mod pong_test {
+    use super::{bedrock_players, PingInfo};
+
+    #[test]
+    fn bedrock_player_count() {
+        let mut ping = PingInfo {
+            source: "127.0.0.1:50000".parse().unwrap(),
+            client_guid: 1,
+            connections: 7,
+            motd: "MCPE;raknet rs;390;1.17.42;0;10;42;Bedrock level;Survival;".to_owned(),
+        };
+        assert_eq!(
+            bedrock_players(&ping),
+            "MCPE;raknet rs;390;1.17.42;7;10;42;Bedrock level;Survival;"
+        );
+        ping.motd = "not bedrock".to_owned();
+        assert_eq!(bedrock_players(&ping), "not bedrock");
+    }
+}
@@ -2,10 +2,21 @@ use std::{fmt::Display, net::SocketAddr};
 
 use crate::packet::RaknetPacket;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DisconnectReason {
     Timeout,
-    Disconnect,
+    //closed by this side
+    Closed,
+    ClosedByPeer,
+    Kicked,
+    Banned,
+    ProtocolError,
+    HandshakeFailed,
+    Shutdown,
+    //the event queue was full, see `OverflowPolicy::Disconnect`
+    Overflow,
+    //a reconnect reached a different server, see `ReconnectPolicy::same_server`
+    ServerChanged,
 }
 
 #[derive(Clone)]
@@ -14,13 +25,28 @@ pub enum RaknetEvent {
     Connected(SocketAddr, u64),
     Disconnected(SocketAddr, u64, DisconnectReason),
     Error(SocketAddr, RaknetError),
+    //the connection timed out, attempt number of the reconnect that is about to start
+    Reconnecting(SocketAddr, u32),
+    //guid of the server that took the connection back, compare it with the one of `Connected`
+    Reconnected(SocketAddr, u64),
+    //offline message from `send_unconnected` of the source address
+    Unconnected(SocketAddr, Vec<u8>),
+    //server that announced itself with `Server::advertise`, its guid and motd
+    Advertisement(SocketAddr, u64, String),
 }
 
 #[derive(Debug, Clone)]
 pub enum RaknetError {
     IncompatibleProtocolVersion(u8, u8), //Server,Client
     AlreadyConnected(SocketAddr),
+    ConnectionBanned(SocketAddr),
     RemoteClosed(SocketAddr),
+    //the handshake got no answer in time
+    TimedOut(SocketAddr),
+    //the server has reached its maximum number of connections
+    NoFreeIncomingConnections(SocketAddr),
+    //every resolved address of the host failed
+    ConnectFailed(Vec<(SocketAddr, RaknetError)>),
     Other(String),
 }
 
@@ -31,7 +57,20 @@ impl Display for RaknetError {
                 write!(f, "Different Protocol Version: {} {}", server, client)
             }
             Self::AlreadyConnected(s) => write!(f, "AlreadyConnected: {}", s),
+            Self::ConnectionBanned(s) => write!(f, "ConnectionBanned: {}", s),
             Self::RemoteClosed(s) => write!(f, "RemoteClosed : {}", s),
+            Self::TimedOut(s) => write!(f, "TimedOut: {}", s),
+            Self::NoFreeIncomingConnections(s) => write!(f, "NoFreeIncomingConnections: {}", s),
+            Self::ConnectFailed(failed) => {
+                write!(f, "ConnectFailed:")?;
+                if failed.is_empty() {
+                    write!(f, " no addresses")?;
+                }
+                for (address, e) in failed {
+                    write!(f, " {} ({})", address, e)?;
+                }
+                Ok(())
+            }
             Self::Other(s) => write!(f, "{}", s),
         }
     }
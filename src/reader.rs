@@ -1,12 +1,12 @@
 use std::{
-    io::{Cursor, Result},
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    io::{Cursor, Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
     str,
 };
 use tokio::io::AsyncReadExt;
 use tokio_byteorder::{AsyncReadBytesExt, BigEndian, LittleEndian};
 
-use crate::packets::MAGIC;
+use crate::{codec::is_af_inet6, packets::MAGIC};
 
 pub enum Endian {
     Big,
@@ -80,40 +80,43 @@ impl<'a> Reader<'a> {
         Ok(magic == MAGIC)
     }
     pub async fn read_address(&mut self) -> Result<SocketAddr> {
-        let ip_ver = self.read_u8().await?;
-
-        if ip_ver == 4 {
-            let ip = Ipv4Addr::new(
-                0xff - self.read_u8().await?,
-                0xff - self.read_u8().await?,
-                0xff - self.read_u8().await?,
-                0xff - self.read_u8().await?,
-            );
-            let port = AsyncReadBytesExt::read_u16::<BigEndian>(&mut self.cursor).await?;
-            Ok(SocketAddr::new(IpAddr::V4(ip), port))
-        } else {
-            self.next(2);
-            let port = AsyncReadBytesExt::read_u16::<LittleEndian>(&mut self.cursor).await?;
-            self.next(4);
-            let mut addr_buf = [0; 16];
-            self.cursor.read_exact(&mut addr_buf).await?;
-
-            let mut address_cursor = Reader::new(&addr_buf);
-            self.next(4);
-            Ok(SocketAddr::new(
-                IpAddr::V6(Ipv6Addr::new(
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                    address_cursor.read_u16(Endian::Big).await?,
-                )),
-                port,
-            ))
-        } //IPv6 address = 128bit = u8 * 16
+        match self.read_u8().await? {
+            4 => {
+                let ip = Ipv4Addr::new(
+                    0xff - self.read_u8().await?,
+                    0xff - self.read_u8().await?,
+                    0xff - self.read_u8().await?,
+                    0xff - self.read_u8().await?,
+                );
+                let port = AsyncReadBytesExt::read_u16::<BigEndian>(&mut self.cursor).await?;
+                Ok(SocketAddr::new(IpAddr::V4(ip), port))
+            }
+            6 => {
+                let family = AsyncReadBytesExt::read_u16::<LittleEndian>(&mut self.cursor).await?;
+                if !is_af_inet6(family) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("unknown address family {}", family),
+                    ));
+                }
+                let port = AsyncReadBytesExt::read_u16::<BigEndian>(&mut self.cursor).await?;
+                let flowinfo = AsyncReadBytesExt::read_u32::<BigEndian>(&mut self.cursor).await?;
+                let mut addr_buf = [0; 16];
+                self.cursor.read_exact(&mut addr_buf).await?;
+                let scope_id =
+                    AsyncReadBytesExt::read_u32::<LittleEndian>(&mut self.cursor).await?;
+                Ok(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr_buf),
+                    port,
+                    flowinfo,
+                    scope_id,
+                )))
+            }
+            version => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown address version {}", version),
+            )),
+        }
     }
     pub fn next(&mut self, n: u64) {
         self.cursor.set_position(self.cursor.position() + n);
@@ -5,7 +5,7 @@ use crate::{packet::SplitPacketQueue, packets::frame::Frame};
 pub struct ReceivedQueue {
     min: u32,
     max: u32,
-    packet_queue: HashMap<u32, Frame>,
+    packet_queue: HashMap<u32, Frame<'static>>,
     splits: SplitPacketQueue,
 }
 impl ReceivedQueue {
@@ -17,7 +17,16 @@ impl ReceivedQueue {
             splits: SplitPacketQueue::new(),
         }
     }
-    pub fn add(&mut self, frame: Frame) {
+    //a complete frame that is next in order is handled without being buffered
+    pub fn take_next(&mut self, frame: &Frame<'_>) -> bool {
+        if frame.split || frame.order_index != self.min {
+            return false;
+        }
+        self.min += 1;
+        self.max = self.max.max(self.min);
+        true
+    }
+    pub fn add(&mut self, frame: Frame<'_>) {
         if frame.split {
             self.splits.add(&frame);
             for mut packet in self.splits.get_and_clear() {
@@ -38,21 +47,18 @@ impl ReceivedQueue {
         if frame.order_index >= self.max {
             self.max = frame.order_index + 1
         }
-        self.packet_queue.insert(frame.order_index, frame);
+        self.packet_queue
+            .insert(frame.order_index, frame.into_owned());
     }
-    pub fn get_all(&mut self) -> Vec<Frame> {
+    pub fn split_reassemblies(&self) -> usize {
+        self.splits.pool.len()
+    }
+    pub fn get_all(&mut self) -> Vec<Frame<'static>> {
         let mut ret = vec![];
-        let mut index = self.min;
-        for o in self.min..self.max {
-            if self.packet_queue.contains_key(&o) {
-                ret.push(self.packet_queue.get(&o).unwrap().clone());
-            } else {
-                break;
-            }
-            self.packet_queue.remove(&o);
-            index += 1;
+        while let Some(frame) = self.packet_queue.remove(&self.min) {
+            ret.push(frame);
+            self.min += 1;
         }
-        self.min = index;
         ret
     }
 }
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueuedMessages {
+    //dropped once the connection is back, the application resends what still matters
+    Discard,
+    //sent in their original order over the new connection
+    Replay,
+}
+
+/// How a `Client` gets its connection back after a timeout, see `Client::set_reconnect`.
+///
+/// Every attempt is announced with `RaknetEvent::Reconnecting` and a successful one
+/// with `RaknetEvent::Reconnected`. Once the attempts are used up the client gives up
+/// with `Disconnected(.., DisconnectReason::Timeout)`.
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    //delay before the first attempt, doubled after every failed one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub max_attempts: u32,
+    //time an attempt gets to finish the handshake
+    pub attempt_timeout: Duration,
+    //a server that answers with another guid, e.g. because it restarted, ends the reconnect
+    //with `DisconnectReason::ServerChanged` instead of being taken
+    pub same_server: bool,
+    //what happens to messages sent while the connection is down
+    pub queued: QueuedMessages,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: 8,
+            attempt_timeout: Duration::from_secs(5),
+            same_server: false,
+            queued: QueuedMessages::Discard,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    //delay before the given attempt, counted from 1
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod reconnect_test {
+    use super::ReconnectPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let delays: Vec<u128> = [1, 2, 3, 4, 5, 40]
+            .iter()
+            .map(|attempt| policy.backoff(*attempt).as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+    }
+}
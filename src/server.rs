@@ -1,193 +1,418 @@
 use rand::random;
-use std::{collections::HashMap, io::Result, net::SocketAddr, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Result,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use tokio::{
     net::UdpSocket,
-    sync::{mpsc::Receiver, Mutex},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        watch, Mutex,
+    },
+    task::JoinHandle,
+};
+
+use crate::transport::{
+    bind_dual_stack, bind_reusable, bind_udp, normalise, outbound, Bind, Dial, Transport,
 };
 
 use crate::macros::*;
-use crate::RaknetEvent;
-use crate::{connection::Connection, packets::*};
+use crate::{
+    client::handshake,
+    connection::{Connection, RaknetType},
+    connectionmap::ConnectionMap,
+    driver::{drive, wait_pong, Cleanup, ConnectionHandle, Slots, INBOUND_CAPACITY},
+    eventqueue::event_queue,
+    handshake::{answer_request1, answer_request2, is_reply, Admission, Answer},
+    packets::*,
+    pong::{PingInfo, PongProvider},
+    time,
+};
+use crate::{
+    DisconnectReason, EventQueueConfig, Latency, Peer, RaknetError, RaknetEvent, RaknetStatistics,
+    RemoteClock,
+};
 
-const RAKNET_PROTOCOL_VERSION: u8 = 0xA;
+const ACCEPT_BACKLOG: usize = 32;
 
-pub struct Server {
-    socket: Option<Arc<UdpSocket>>,
-    connection: Arc<Mutex<HashMap<SocketAddr, Arc<Mutex<Connection>>>>>,
+//offline messages waiting for `recv`, further ones are dropped
+pub(crate) const UNCONNECTED_BACKLOG: usize = 64;
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MTU: u16 = 1492;
+
+//where `listen` gets its transport from
+enum Source<T> {
+    Bind(Bind<T>),
+    Shared(Arc<T>),
+}
+
+/// RakNet's peer: it accepts connections, opens its own with `connect`
+/// over the same socket, and reports all of them through `recv` and `accept`.
+pub struct Server<T = UdpSocket> {
+    socket: Option<Arc<T>>,
+    listener: Option<Arc<Listener<T>>>,
+    source: Source<T>,
+    connection: Arc<ConnectionMap>,
     title: Arc<Mutex<String>>,
-    connected_clients: Arc<Mutex<Vec<u64>>>,
-    receivers: Arc<Mutex<Vec<Receiver<RaknetEvent>>>>,
+    pong_provider: Arc<std::sync::Mutex<Option<PongProvider>>>,
+    connected_clients: Arc<std::sync::Mutex<Vec<u64>>>,
+    //admitted handshakes and connections, the player count only shows the established ones
+    slots: Arc<Slots>,
+    banned: Arc<Mutex<HashSet<IpAddr>>>,
+    incoming: Arc<Mutex<Receiver<Peer>>>,
+    incoming_sender: Sender<Peer>,
+    unconnected: Mutex<Receiver<RaknetEvent>>,
+    unconnected_sender: Sender<RaknetEvent>,
+    dropped_unconnected: Arc<AtomicU64>,
+    peers: Arc<Mutex<Vec<Peer>>>,
+    event_queue: EventQueueConfig,
+    closing: Arc<AtomicBool>,
+    max_connections: Arc<AtomicUsize>,
+    lan_port: Option<u16>,
+    tasks: Vec<JoinHandle<()>>,
+    //connection drivers stop once this is dropped or signalled
+    stop: watch::Sender<()>,
     pub local_addr: SocketAddr,
     pub id: u64,
 }
 
-impl Server {
+impl Server<UdpSocket> {
+    //binds a UDP socket on `listen`
     pub fn new(address: SocketAddr, title: String) -> Self {
+        Self::build(address, title, Source::Bind(bind_udp))
+    }
+
+    /// Listens on `port` of every IPv6 and IPv4 address with one socket.
+    /// IPv4 peers keep their IPv4 address in events and lookups.
+    pub fn dual_stack(port: u16, title: String) -> Self {
+        let address = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port);
+        Self::build(address, title, Source::Bind(bind_dual_stack))
+    }
+}
+
+impl<T: Transport> Server<T> {
+    /// Serves over `transport` instead of binding a UDP socket.
+    pub fn with_transport(transport: T, title: String) -> Result<Self> {
+        let address = transport.local_addr()?;
+        Ok(Self::build(
+            address,
+            title,
+            Source::Shared(Arc::new(transport)),
+        ))
+    }
+
+    fn build(address: SocketAddr, title: String, source: Source<T>) -> Self {
+        let (incoming_sender, incoming) = tokio::sync::mpsc::channel::<Peer>(ACCEPT_BACKLOG);
+        let (unconnected_sender, unconnected) = tokio::sync::mpsc::channel(UNCONNECTED_BACKLOG);
         Self {
             socket: None,
-            connection: Arc::new(Mutex::new(HashMap::new())),
+            listener: None,
+            source,
+            connection: Arc::new(ConnectionMap::new()),
             id: random::<u64>(),
             title: Arc::new(Mutex::new(title)),
+            pong_provider: Arc::new(std::sync::Mutex::new(None)),
             local_addr: address,
-            connected_clients: Arc::new(Mutex::new(vec![])),
-            receivers: Arc::new(Mutex::new(vec![])),
+            connected_clients: Arc::new(std::sync::Mutex::new(vec![])),
+            slots: Arc::new(Slots::default()),
+            banned: Arc::new(Mutex::new(HashSet::new())),
+            incoming: Arc::new(Mutex::new(incoming)),
+            incoming_sender,
+            unconnected: Mutex::new(unconnected),
+            unconnected_sender,
+            dropped_unconnected: Arc::new(AtomicU64::new(0)),
+            peers: Arc::new(Mutex::new(vec![])),
+            event_queue: EventQueueConfig::default(),
+            closing: Arc::new(AtomicBool::new(false)),
+            max_connections: Arc::new(AtomicUsize::new(usize::MAX)),
+            lan_port: None,
+            tasks: vec![],
+            stop: watch::channel(()).0,
         }
     }
 
     pub async fn listen(&mut self) -> std::io::Result<()> {
-        self.socket = Some(Arc::new(UdpSocket::bind(self.local_addr).await?));
-        let socket2 = self.socket.clone().unwrap();
-        let connections2 = self.connection.clone();
-        let connected_client = self.connected_clients.clone();
-        let id = self.id;
-        let motd = self.title.clone();
-        let receiver = self.receivers.clone();
-        tokio::spawn(async move {
+        let socket = match &self.source {
+            Source::Bind(bind) => Arc::new(bind(self.local_addr).await?),
+            Source::Shared(transport) => transport.clone(),
+        };
+        self.local_addr = socket.local_addr()?;
+        self.socket = Some(socket);
+        self.closing.store(false, Ordering::SeqCst);
+        let listener = Arc::new(Listener {
+            socket: self.socket.clone().unwrap(),
+            local: self.local_addr,
+            connections: self.connection.clone(),
+            connected_clients: self.connected_clients.clone(),
+            slots: self.slots.clone(),
+            id: self.id,
+            motd: self.title.clone(),
+            pong_provider: self.pong_provider.clone(),
+            incoming: self.incoming_sender.clone(),
+            unconnected: self.unconnected_sender.clone(),
+            dropped_unconnected: self.dropped_unconnected.clone(),
+            event_queue: self.event_queue,
+            closing: self.closing.clone(),
+            max_connections: self.max_connections.clone(),
+            banned: self.banned.clone(),
+            dialing: std::sync::Mutex::new(HashMap::new()),
+            stop: self.stop.subscribe(),
+        });
+        self.listener = Some(listener.clone());
+        self.tasks.push(tokio::spawn(async move {
             let mut v = [0u8; 1500];
             loop {
-                let (size, source) = unwrap_or_return!(socket2.recv_from(&mut v).await);
+                let (size, source) = unwrap_or_return!(listener.socket.recv_from(&mut v).await);
+                let source = normalise(source);
 
                 if size == 0 {
                     continue;
                 }
 
-                let connections3 = connections2.clone();
-                let socket3 = socket2.clone();
-                let connected_client2 = connected_client.clone();
-                let motd2 = motd.clone();
-                let receiver2 = receiver.clone();
-
-                tokio::spawn(async move {
-                    if !connections3.lock().await.contains_key(&source) {
-                        //not connected
-                        let buff = &v[..size];
-                        match buff[0] {
-                            UnconnectedPing::ID => {
-                                let p = unwrap_or_return!(decode::<UnconnectedPing>(buff).await);
-                                let pong = UnconnectedPong::new(
-                                    p.time,
-                                    id,
-                                    motd2.lock().await.to_string(),
-                                );
-                                let data = unwrap_or_dbg!(encode(pong).await);
-                                unwrap_or_dbg!(socket3.send_to(&data, source).await);
-                            }
-                            OpenConnectionRequest1::ID => {
-                                let p =
-                                    unwrap_or_return!(decode::<OpenConnectionRequest1>(buff).await);
-                                if p.protocol_version == RAKNET_PROTOCOL_VERSION {
-                                    let ocreply1 = OpenConnectionReply1::new(id, false, p.mtu_size);
-                                    let data = unwrap_or_dbg!(encode(ocreply1).await);
-                                    unwrap_or_dbg!(socket3.send_to(&data, source).await);
-                                } else {
-                                    let reply = IncompatibleProtocolVersion::new(
-                                        RAKNET_PROTOCOL_VERSION,
-                                        id,
-                                    );
-                                    let data = unwrap_or_dbg!(encode(reply).await);
-                                    unwrap_or_dbg!(socket3.send_to(&data, source).await);
-                                }
-                            }
-                            OpenConnectionRequest2::ID => {
-                                let p =
-                                    unwrap_or_return!(decode::<OpenConnectionRequest2>(buff).await);
-                                if connected_client2.lock().await.contains(&p.guid) {
-                                    let already_connected = AlreadyConnected::new(id);
-                                    let data = unwrap_or_dbg!(encode(already_connected).await);
-                                    unwrap_or_dbg!(socket3.send_to(&data, source).await);
-                                    return;
-                                }
-
-                                let ocreply2 = OpenConnectionReply2::new(id, source, p.mtu, false);
-                                let data = unwrap_or_dbg!(encode(ocreply2).await);
-                                unwrap_or_dbg!(socket3.send_to(&data, source).await);
-                                let (s, r) = tokio::sync::mpsc::channel::<RaknetEvent>(10);
-                                connections3.lock().await.insert(
-                                    source,
-                                    Arc::new(Mutex::new(Connection::new(
-                                        source,
-                                        socket3.clone(),
-                                        id,
-                                        p.guid,
-                                        p.mtu,
-                                        s,
-                                        crate::connection::RaknetType::Server,
-                                    ))),
-                                );
-                                connected_client2.lock().await.push(p.guid);
-                                receiver2.lock().await.push(r);
-                                //connected!
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        connections3
-                            .lock()
-                            .await
-                            .get_mut(&source)
-                            .unwrap()
-                            .lock()
-                            .await
-                            .handle(&v[..size])
-                            .await;
+                //the connection's driver handles its datagrams in arrival order,
+                //offline messages may come from connected peers as well
+                match listener.connections.inbound(&source) {
+                    Some(_) if v[0] == OutOfBand::ID || v[0] == AdvertiseSystem::ID => {
+                        listener.handle_offline(&v[..size], source).await
                     }
-                });
+                    Some(inbound) => {
+                        let _ = inbound.try_send(v[..size].to_vec());
+                    }
+                    None => listener.handle_offline(&v[..size], source).await,
+                }
             }
-        });
+        }));
 
-        let connections = self.connection.clone();
-
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-                for conn in connections.lock().await.values() {
-                    let conn2 = conn.clone();
-                    tokio::spawn(async move {
-                        conn2.lock().await.update().await;
-                    });
+        if let Some(port) = self.lan_port {
+            let lan = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port);
+            let lan = match bind_reusable(lan) {
+                Ok(socket) => socket,
+                Err(_) => bind_reusable(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))?,
+            };
+            let listener = self.listener.clone().unwrap();
+            self.tasks.push(tokio::spawn(async move {
+                let mut v = [0u8; 1500];
+                loop {
+                    //one unreachable pinger must not end the discovery
+                    let (size, source) = unwrap_or_continue!(lan.recv_from(&mut v).await);
+                    if size == 0 || listener.closing.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    if let Some(pong) = listener.answer_ping(&v[..size], normalise(source)).await {
+                        let _ = lan.send_to(&pong, source).await;
+                    }
                 }
-            }
-        });
+            }));
+        }
 
         Ok(())
     }
 
+    /// Also answers pings sent to the broadcast address or an IPv6 multicast group
+    /// on `port`, like Bedrock LAN games on 19132, while connections stay on the
+    /// listening address. Other LAN games may share the port. Takes effect on `listen`.
+    pub fn set_lan_discovery(&mut self, port: Option<u16>) {
+        self.lan_port = port;
+    }
+
+    /// Disconnects every peer, waits until their pending reliable data is acknowledged
+    /// or a deadline passes, then stops the background tasks and releases the socket.
+    pub async fn shutdown(&mut self) {
+        self.closing.store(true, Ordering::SeqCst);
+        let connections = self.connection.values();
+        for conn in connections.iter() {
+            conn.close(DisconnectReason::Shutdown);
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !self.tasks.is_empty() && !self.connection.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        for task in self.tasks.drain(..) {
+            task.abort();
+            let _ = task.await;
+        }
+        let _ = self.stop.send(());
+        if !self.connection.is_empty() {
+            //nobody took the events of these peers in time, their queues go away with them
+            self.peers.lock().await.clear();
+            if let Ok(mut incoming) = self.incoming.try_lock() {
+                while incoming.try_recv().is_ok() {}
+            }
+        }
+        self.connection.clear();
+        self.connected_clients.lock().unwrap().clear();
+        self.listener = None;
+
+        if let Some(socket) = self.socket.take() {
+            //stopped drivers still hold the socket for a moment
+            let kept = if let Source::Shared(_) = self.source {
+                2
+            } else {
+                1
+            };
+            let released = deadline.max(Instant::now() + Duration::from_millis(100));
+            while Arc::strong_count(&socket) > kept && Instant::now() < released {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+    }
+
+    /// Waits for the next connection that completed the handshake.
+    /// Connections taken here are not reported by `recv`.
+    pub async fn accept(&self) -> Result<Peer> {
+        match self.incoming.lock().await.recv().await {
+            Some(peer) => Ok(peer),
+            None => Err(std::io::Error::other("Server closed")),
+        }
+    }
+
+    /// Opens a connection to another peer over the listening socket and
+    /// returns its guid once the handshake is done.
+    /// The connection is then reported by `recv` or `accept` like an incoming one.
+    pub async fn connect(&self, address: SocketAddr) -> std::result::Result<u64, RaknetError> {
+        let listener = match self.listener.as_ref() {
+            Some(listener) => listener,
+            None => return Err(RaknetError::Other("Server is not listening".to_owned())),
+        };
+        listener.connect(normalise(address)).await
+    }
+
+    /// The events of every peer and the offline messages.
+    /// Peers accepted meanwhile are taken from `accept` unless it is waiting,
+    /// next to `accept` use `recv_unconnected` instead.
     pub async fn recv(&self) -> Result<Vec<RaknetEvent>> {
-        let mut events: Vec<RaknetEvent> = vec![];
-        let mut disconnected_clients = vec![];
+        let mut peers = self.peers.lock().await;
+        let mut events = self.recv_unconnected().await?;
 
-        for (index, receiver) in self.receivers.lock().await.iter_mut().enumerate() {
-            while let Ok(event) = receiver.try_recv() {
-                if let RaknetEvent::Disconnected(addr, _guid, _reason) = event {
-                    disconnected_clients.push((addr, index));
-                }
+        //`accept` holds the lock while it waits, in which case it takes the new peers
+        if let Ok(mut incoming) = self.incoming.try_lock() {
+            while let Ok(peer) = incoming.try_recv() {
+                events.push(RaknetEvent::Connected(peer.remote_addr(), peer.guid()));
+                peers.push(peer);
+            }
+        }
+        for peer in peers.iter_mut() {
+            while let Some(event) = peer.try_recv() {
                 events.push(event);
             }
         }
-        for addr in disconnected_clients.iter() {
-            self.connection.lock().await.remove(&addr.0);
-            self.connected_clients.lock().await.remove(addr.1);
-            self.receivers.lock().await.remove(addr.1);
+        peers.retain(|peer| !peer.is_closed());
+        Ok(events)
+    }
+
+    //only `Unconnected` and `Advertisement`, peers are left to `accept`
+    pub async fn recv_unconnected(&self) -> Result<Vec<RaknetEvent>> {
+        let mut events = vec![];
+        let mut unconnected = self.unconnected.lock().await;
+        while let Ok(event) = unconnected.try_recv() {
+            events.push(event);
         }
-        disconnected_clients.clear();
         Ok(events)
     }
 
+    //offline messages dropped because they were not received in time
+    pub fn dropped_unconnected(&self) -> u64 {
+        self.dropped_unconnected.load(Ordering::Relaxed)
+    }
+
+    /// Sends `buff` to `addr` without a connection, it arrives as `RaknetEvent::Unconnected`.
+    /// At most `MAX_OUT_OF_BAND` bytes.
+    pub async fn send_unconnected(&self, addr: SocketAddr, buff: &[u8]) -> Result<()> {
+        let socket = match self.socket.as_ref() {
+            Some(socket) => socket,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Server is not listening",
+                ))
+            }
+        };
+        let payload = encode_out_of_band(self.id, buff)?;
+        let target = outbound(normalise(addr), &self.local_addr);
+        socket.send_to(&payload, target).await?;
+        Ok(())
+    }
+
     pub async fn send_to(&mut self, addr: &SocketAddr, buff: &[u8]) -> Result<()> {
-        if !self.connection.lock().await.contains_key(addr) {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Not connected",
-            ));
-        }
-        self.connection
-            .lock()
-            .await
-            .get_mut(addr)
-            .unwrap()
-            .lock()
-            .await
-            .send_to(buff);
+        match self.connection.get(addr) {
+            Some(conn) => {
+                conn.send(buff);
+                Ok(())
+            }
+            None => Err(std::io::Error::other("Not connected")),
+        }
+    }
+
+    //takes effect for connections established afterwards
+    pub fn set_event_queue(&mut self, config: EventQueueConfig) {
+        self.event_queue = config;
+    }
+
+    pub async fn stats(&self, addr: &SocketAddr) -> Option<RaknetStatistics> {
+        Some(self.connection.get(addr)?.stats())
+    }
+
+    pub async fn latency(&self, addr: &SocketAddr) -> Option<Latency> {
+        self.connection.get(addr)?.latency()
+    }
+
+    pub async fn remote_clock(&self, addr: &SocketAddr) -> Option<RemoteClock> {
+        self.connection.get(addr)?.remote_clock()
+    }
+
+    //sends a connected ping and waits for the round trip time in milliseconds
+    pub async fn ping(&self, addr: &SocketAddr) -> Result<u128> {
+        let conn = match self.connection.get(addr) {
+            Some(conn) => conn,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Not connected",
+                ))
+            }
+        };
+        wait_pong(conn.ping()).await
+    }
+
+    //events of the connection discarded because its queue was full
+    pub async fn dropped_events(&self, addr: &SocketAddr) -> Option<u64> {
+        Some(self.connection.get(addr)?.dropped_events())
+    }
+
+    /// Connections beyond `max` are refused with `NoFreeIncomingConnections`
+    /// and `UnconnectedPingOpenConnections` is no longer answered. Unlimited by default.
+    pub fn set_max_connections(&mut self, max: usize) {
+        self.max_connections.store(max, Ordering::SeqCst);
+    }
+
+    /// Announces the server with its motd to `addr` without being pinged,
+    /// it arrives as `RaknetEvent::Advertisement`.
+    pub async fn advertise(&self, addr: SocketAddr) -> Result<()> {
+        let socket = match self.socket.as_ref() {
+            Some(socket) => socket,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Server is not listening",
+                ))
+            }
+        };
+        let motd = self.title.lock().await.to_string();
+        let advertisement = AdvertiseSystem::new(time() as i64, self.id, motd);
+        let target = outbound(normalise(addr), &self.local_addr);
+        socket.send_to(&encode(advertisement), target).await?;
         Ok(())
     }
 
@@ -197,18 +422,281 @@ impl Server {
         Ok(())
     }
 
+    /// Builds the pong of every ping with `provider` instead of sending the motd as is,
+    /// e.g. `bedrock_players` to keep the player count of a Bedrock motd current.
+    /// Takes effect immediately.
+    pub fn set_pong_provider<F>(&mut self, provider: F)
+    where
+        F: Fn(&PingInfo) -> String + Send + Sync + 'static,
+    {
+        *self.pong_provider.lock().unwrap() = Some(Arc::new(provider));
+    }
+
+    //pongs carry the motd as is again
+    pub fn clear_pong_provider(&mut self) {
+        *self.pong_provider.lock().unwrap() = None;
+    }
+
     pub async fn disconnect(&mut self, addr: SocketAddr) {
-        if !self.connection.lock().await.contains_key(&addr) {
+        self.close(addr, DisconnectReason::Closed).await;
+    }
+
+    pub async fn kick(&mut self, addr: SocketAddr) {
+        self.close(addr, DisconnectReason::Kicked).await;
+    }
+
+    //disconnects the address and rejects its connection attempts until `unban`
+    pub async fn ban(&mut self, addr: SocketAddr) {
+        self.banned.lock().await.insert(normalise(addr).ip());
+        self.close(addr, DisconnectReason::Banned).await;
+    }
+
+    pub async fn unban(&mut self, ip: IpAddr) {
+        self.banned.lock().await.remove(&ip.to_canonical());
+    }
+
+    async fn close(&self, addr: SocketAddr, reason: DisconnectReason) {
+        let conn = self.connection.get(&addr);
+        if let Some(conn) = conn {
+            conn.close(reason);
+        }
+    }
+}
+
+impl<T> Drop for Server<T> {
+    fn drop(&mut self) {
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+    }
+}
+
+struct Listener<T> {
+    socket: Arc<T>,
+    local: SocketAddr,
+    connections: Arc<ConnectionMap>,
+    connected_clients: Arc<std::sync::Mutex<Vec<u64>>>,
+    slots: Arc<Slots>,
+    id: u64,
+    motd: Arc<Mutex<String>>,
+    pong_provider: Arc<std::sync::Mutex<Option<PongProvider>>>,
+    incoming: Sender<Peer>,
+    unconnected: Sender<RaknetEvent>,
+    dropped_unconnected: Arc<AtomicU64>,
+    event_queue: EventQueueConfig,
+    closing: Arc<AtomicBool>,
+    max_connections: Arc<AtomicUsize>,
+    banned: Arc<Mutex<HashSet<IpAddr>>>,
+    //offline replies of the peers `connect` is handshaking with
+    dialing: std::sync::Mutex<HashMap<SocketAddr, Sender<Vec<u8>>>>,
+    stop: watch::Receiver<()>,
+}
+
+impl<T: Transport> Listener<T> {
+    async fn connect(&self, address: SocketAddr) -> std::result::Result<u64, RaknetError> {
+        if self.connections.get(&address).is_some() {
+            return Err(RaknetError::AlreadyConnected(address));
+        }
+        let (replies, receiver) = mpsc::channel(INBOUND_CAPACITY);
+        {
+            let mut dialing = self.dialing.lock().unwrap();
+            //the handshake under way would lose its replies to the new one
+            if dialing.contains_key(&address) {
+                return Err(RaknetError::AlreadyConnected(address));
+            }
+            dialing.insert(address, replies);
+        }
+        let dial = Dial::new(self.socket.clone(), address, receiver);
+        let handshake = handshake(&dial, address, self.local, self.id, MTU);
+        let server_guid = tokio::time::timeout(CONNECT_TIMEOUT, handshake).await;
+        self.dialing.lock().unwrap().remove(&address);
+        let server_guid = match server_guid {
+            Ok(p) => p?,
+            Err(_) => return Err(RaknetError::TimedOut(address)),
+        };
+
+        let (s, mut r) = event_queue(self.event_queue);
+        let mut connection = Connection::new(
+            address,
+            self.id,
+            server_guid,
+            MTU,
+            RaknetType::Client,
+            time(),
+        );
+        connection.connect(time());
+        let connection = Arc::new(ConnectionHandle::new(connection, s).counted(self.slots.clone()));
+        if !self.start(address, connection.clone(), server_guid).await {
+            return Err(RaknetError::AlreadyConnected(address));
+        }
+        match r.recv().await {
+            Some(RaknetEvent::Connected(addr, guid)) => {
+                let peer = Peer::new(connection, r, addr, guid);
+                if self.incoming.send(peer).await.is_err() {
+                    return Err(RaknetError::Other("Server closed".to_owned()));
+                }
+                Ok(guid)
+            }
+            Some(RaknetEvent::Disconnected(_, _, reason)) => Err(match reason {
+                DisconnectReason::HandshakeFailed | DisconnectReason::Timeout => {
+                    RaknetError::TimedOut(address)
+                }
+                DisconnectReason::ClosedByPeer => RaknetError::RemoteClosed(address),
+                //closed on this side while the handshake was under way
+                reason => RaknetError::Other(format!("Connection closed: {:?}", reason)),
+            }),
+            Some(RaknetEvent::Error(_, e)) => Err(e),
+            Some(_) => Err(RaknetError::Other(
+                "Unexpected event before Connected".to_owned(),
+            )),
+            None => Err(RaknetError::Other("Server closed".to_owned())),
+        }
+    }
+
+    //drives the connection until it is finished, false if the address already has one
+    async fn start(
+        &self,
+        source: SocketAddr,
+        connection: Arc<ConnectionHandle>,
+        guid: u64,
+    ) -> bool {
+        let (inbound, inbound_receiver) = tokio::sync::mpsc::channel(INBOUND_CAPACITY);
+        if !self.connections.insert(source, connection.clone(), inbound) {
+            return false;
+        }
+        self.connected_clients.lock().unwrap().push(guid);
+        self.slots.reserve();
+
+        let connections = self.connections.clone();
+        let connected_clients = self.connected_clients.clone();
+        let slots = self.slots.clone();
+        let handle = connection.clone();
+        //also when the driver panics or never runs, the address, guid and slot must not stay taken
+        let cleanup = Cleanup::new(move || {
+            connections.remove(&source);
+            connected_clients.lock().unwrap().retain(|g| *g != guid);
+            slots.release(handle.connected());
+        });
+        let socket = self.socket.clone();
+        let stop = self.stop.clone();
+        tokio::spawn(async move {
+            let _cleanup = cleanup;
+            drive(connection, socket, inbound_receiver, stop).await;
+        });
+        true
+    }
+
+    //the pong for an unconnected ping, if it gets one
+    async fn answer_ping(&self, buff: &[u8], source: SocketAddr) -> Option<Vec<u8>> {
+        let (time, client_guid) = match buff[0] {
+            UnconnectedPing::ID => {
+                let ping = decode::<UnconnectedPing>(buff).ok()?;
+                (ping.time, ping.guid)
+            }
+            UnconnectedPingOpenConnections::ID if self.has_free_slot() => {
+                let ping = decode::<UnconnectedPingOpenConnections>(buff).ok()?;
+                (ping.time, ping.guid)
+            }
+            _ => return None,
+        };
+        let motd = self.motd.lock().await.to_string();
+        let provider = self.pong_provider.lock().unwrap().clone();
+        let motd = match provider {
+            Some(provider) => provider(&PingInfo {
+                source,
+                client_guid,
+                connections: self.slots.established(),
+                motd,
+            }),
+            None => motd,
+        };
+        Some(encode(UnconnectedPong::new(time, self.id, motd)))
+    }
+
+    //offline messages wait for `recv` in a bounded queue
+    fn report(&self, event: RaknetEvent) {
+        if self.unconnected.try_send(event).is_err() {
+            self.dropped_unconnected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    //half-open handshakes hold their slot until they complete or time out
+    fn has_free_slot(&self) -> bool {
+        self.slots.taken() < self.max_connections.load(Ordering::SeqCst)
+    }
+
+    async fn handle_offline(&self, buff: &[u8], source: SocketAddr) {
+        if self.closing.load(Ordering::SeqCst) {
             return;
         }
+        if buff[0] == OutOfBand::ID {
+            let message = unwrap_or_return!(decode::<OutOfBand>(buff));
+            self.report(RaknetEvent::Unconnected(source, message.data));
+            return;
+        }
+        //pings and other offline messages of the dialed peer are handled as usual
+        if is_reply(buff[0]) {
+            let dialing = self.dialing.lock().unwrap().get(&source).cloned();
+            if let Some(replies) = dialing {
+                let _ = replies.try_send(buff.to_vec());
+                return;
+            }
+        }
+        let id = self.id;
+        let target = outbound(source, &self.local);
+        match buff[0] {
+            UnconnectedPing::ID | UnconnectedPingOpenConnections::ID => {
+                if let Some(pong) = self.answer_ping(buff, source).await {
+                    unwrap_or_dbg!(self.socket.send_to(&pong, target).await);
+                }
+            }
+            AdvertiseSystem::ID => {
+                let p = unwrap_or_return!(decode::<AdvertiseSystem>(buff));
+                self.report(RaknetEvent::Advertisement(source, p.guid, p.data));
+            }
+            OpenConnectionRequest1::ID => {
+                let banned = self.banned.lock().await.contains(&source.ip());
+                let reply = unwrap_or_return!(answer_request1(buff, id, banned));
+                unwrap_or_dbg!(self.socket.send_to(&reply, target).await);
+            }
+            OpenConnectionRequest2::ID => {
+                let banned = self.banned.lock().await.contains(&source.ip());
+                let full = !self.has_free_slot();
+                let answer = {
+                    let connected = self.connected_clients.lock().unwrap();
+                    let admission = Admission {
+                        banned,
+                        full,
+                        connected: &connected,
+                    };
+                    unwrap_or_return!(answer_request2(buff, source, id, &admission))
+                };
+                let (reply2, guid, mtu) = match answer {
+                    Answer::Reply(reply) => {
+                        unwrap_or_dbg!(self.socket.send_to(&reply, target).await);
+                        return;
+                    }
+                    Answer::Accept(reply2, guid, mtu) => (reply2, guid, mtu),
+                };
+                unwrap_or_dbg!(self.socket.send_to(&reply2, target).await);
+                let (s, mut r) = event_queue(self.event_queue);
+                let connection = Connection::new(source, id, guid, mtu, RaknetType::Server, time());
+                let connection =
+                    Arc::new(ConnectionHandle::new(connection, s).counted(self.slots.clone()));
+                if !self.start(source, connection.clone(), guid).await {
+                    return;
+                }
 
-        self.connection
-            .lock()
-            .await
-            .get_mut(&addr)
-            .unwrap()
-            .lock()
-            .await
-            .disconnect();
+                //hand the peer over once the handshake has completed
+                let incoming = self.incoming.clone();
+                tokio::spawn(async move {
+                    if let Some(RaknetEvent::Connected(addr, guid)) = r.recv().await {
+                        let peer = Peer::new(connection, r, addr, guid);
+                        unwrap_or_return!(incoming.send(peer).await);
+                    }
+                });
+            }
+            _ => {}
+        }
     }
 }
@@ -0,0 +1,64 @@
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RaknetStatistics {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub datagrams_sent: u64,
+    pub datagrams_received: u64,
+    //datagrams carrying messages, without ACKs and NACKs
+    pub frame_sets_sent: u64,
+    pub retransmissions: u64,
+    pub nacks_sent: u64,
+    pub nacks_received: u64,
+    //retransmitted frame sets / sent frame sets
+    pub packet_loss: f32,
+    //smoothed from ACKs, in milliseconds
+    pub rtt: u128,
+    pub unacknowledged_bytes: usize,
+    pub queued_frames: usize,
+    pub split_reassemblies: usize,
+    //datagrams we are waiting for
+    pub missing_datagrams: usize,
+    pub dropped_events: u64,
+    //datagrams the transport failed to send, handled like lost ones
+    pub send_errors: u64,
+}
+
+//round trip times measured with connected pings, in milliseconds
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Latency {
+    pub last: u128,
+    pub average: u128,
+    pub min: u128,
+    pub max: u128,
+    pub samples: u64,
+    total: u128,
+}
+
+impl Latency {
+    pub(crate) fn add(&mut self, rtt: u128) {
+        if self.samples == 0 {
+            self.min = rtt;
+            self.max = rtt;
+        } else {
+            self.min = self.min.min(rtt);
+            self.max = self.max.max(rtt);
+        }
+        self.samples += 1;
+        self.total += rtt;
+        self.average = self.total / self.samples as u128;
+        self.last = rtt;
+    }
+}
+
+#[test]
+fn latency() {
+    let mut latency = Latency::default();
+    for rtt in [30, 10, 50] {
+        latency.add(rtt);
+    }
+    assert_eq!(latency.last, 50);
+    assert_eq!(latency.average, 30);
+    assert_eq!(latency.min, 10);
+    assert_eq!(latency.max, 50);
+    assert_eq!(latency.samples, 3);
+}
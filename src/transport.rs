@@ -0,0 +1,190 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    future::Future,
+    io::Result,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{mpsc::Receiver, Mutex},
+};
+
+/// Moves datagrams for an endpoint.
+///
+/// `UdpSocket` is the default, anything else can be handed to
+/// `Server::with_transport`, `Client::with_transport` or `Ping::with_transport`.
+/// Every call may come from several tasks at once.
+pub trait Transport: Send + Sync + 'static {
+    fn send_to(&self, buf: &[u8], target: SocketAddr)
+        -> impl Future<Output = Result<usize>> + Send;
+
+    fn recv_from(&self, buf: &mut [u8])
+        -> impl Future<Output = Result<(usize, SocketAddr)>> + Send;
+
+    fn local_addr(&self) -> Result<SocketAddr>;
+}
+
+impl Transport for UdpSocket {
+    fn send_to(
+        &self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> impl Future<Output = Result<usize>> + Send {
+        UdpSocket::send_to(self, buf, target)
+    }
+
+    fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<(usize, SocketAddr)>> + Send {
+        UdpSocket::recv_from(self, buf)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+}
+
+//lets several endpoints share one transport
+impl<T: Transport> Transport for Arc<T> {
+    fn send_to(
+        &self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> impl Future<Output = Result<usize>> + Send {
+        (**self).send_to(buf, target)
+    }
+
+    fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<(usize, SocketAddr)>> + Send {
+        (**self).recv_from(buf)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        (**self).local_addr()
+    }
+}
+
+pub(crate) type Bind<T> = fn(SocketAddr) -> Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+pub(crate) fn bind_udp(
+    address: SocketAddr,
+) -> Pin<Box<dyn Future<Output = Result<UdpSocket>> + Send>> {
+    Box::pin(UdpSocket::bind(address))
+}
+
+//one IPv6 socket that takes IPv4 peers as well, regardless of the platform default
+pub(crate) fn bind_dual_stack(
+    address: SocketAddr,
+) -> Pin<Box<dyn Future<Output = Result<UdpSocket>> + Send>> {
+    Box::pin(async move {
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(false)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&address.into())?;
+        UdpSocket::from_std(socket.into())
+    })
+}
+
+//several LAN games share the discovery port and all of them hear its broadcasts
+pub(crate) fn bind_reusable(address: SocketAddr) -> Result<UdpSocket> {
+    let domain = match address {
+        SocketAddr::V4(_) => Domain::IPV4,
+        SocketAddr::V6(_) => Domain::IPV6,
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    #[cfg(all(
+        unix,
+        not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+    ))]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+//IPv4 peers of a dual-stack socket show up as ::ffff:a.b.c.d, they are known by their IPv4 form
+pub(crate) fn normalise(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), v6.port()),
+            None => address,
+        },
+        SocketAddr::V4(_) => address,
+    }
+}
+
+//an IPv6 socket reaches IPv4 peers through their mapped form
+pub(crate) fn outbound(address: SocketAddr, local: &SocketAddr) -> SocketAddr {
+    match (address, local) {
+        (SocketAddr::V4(v4), SocketAddr::V6(_)) => {
+            SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port())
+        }
+        _ => address,
+    }
+}
+
+//handshakes over a socket that another task reads, that task forwards the peer's offline replies
+pub(crate) struct Dial<T> {
+    socket: Arc<T>,
+    remote: SocketAddr,
+    replies: Mutex<Receiver<Vec<u8>>>,
+}
+
+impl<T> Dial<T> {
+    pub fn new(socket: Arc<T>, remote: SocketAddr, replies: Receiver<Vec<u8>>) -> Self {
+        Self {
+            socket,
+            remote,
+            replies: Mutex::new(replies),
+        }
+    }
+}
+
+impl<T: Transport> Transport for Dial<T> {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        self.socket.send_to(buf, target).await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        match self.replies.lock().await.recv().await {
+            Some(reply) => {
+                let size = reply.len().min(buf.len());
+                buf[..size].copy_from_slice(&reply[..size]);
+                Ok((size, self.remote))
+            }
+            None => Err(std::io::Error::other("Server closed")),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod transport_test {
+    use super::{normalise, outbound};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn mapped_addresses() {
+        let v4: SocketAddr = "127.0.0.1:19132".parse().unwrap();
+        let mapped: SocketAddr = "[::ffff:127.0.0.1]:19132".parse().unwrap();
+        let v6: SocketAddr = "[::1]:19132".parse().unwrap();
+        assert_eq!(normalise(mapped), v4);
+        assert_eq!(normalise(v6), v6);
+        assert_eq!(outbound(v4, &"[::]:0".parse().unwrap()), mapped);
+        assert_eq!(outbound(v4, &"0.0.0.0:0".parse().unwrap()), v4);
+        assert_eq!(outbound(v6, &"[::]:0".parse().unwrap()), v6);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::codec::AF_INET6;
 use crate::packets::MAGIC;
 use crate::reader::Endian;
 use std::{
@@ -91,15 +92,20 @@ impl Writer {
             AsyncWriteBytesExt::write_u16::<BigEndian>(&mut self.cursor, address.port()).await?;
             Ok(())
         } else {
-            AsyncWriteBytesExt::write_i16::<LittleEndian>(&mut self.cursor, 23).await?;
+            let (flowinfo, scope_id) = match address {
+                SocketAddr::V6(address) => (address.flowinfo(), address.scope_id()),
+                SocketAddr::V4(_) => (0, 0),
+            };
+            AsyncWriteBytesExt::write_u8(&mut self.cursor, 0x6).await?;
+            AsyncWriteBytesExt::write_u16::<LittleEndian>(&mut self.cursor, AF_INET6).await?;
             AsyncWriteBytesExt::write_u16::<BigEndian>(&mut self.cursor, address.port()).await?;
-            AsyncWriteBytesExt::write_i32::<BigEndian>(&mut self.cursor, 0).await?;
+            AsyncWriteBytesExt::write_u32::<BigEndian>(&mut self.cursor, flowinfo).await?;
             let ip_bytes = match address.ip() {
                 IpAddr::V6(ip) => ip.octets().to_vec(),
                 _ => vec![0; 16],
             };
             self.write(&ip_bytes).await?;
-            AsyncWriteBytesExt::write_i32::<BigEndian>(&mut self.cursor, 0).await?;
+            AsyncWriteBytesExt::write_u32::<LittleEndian>(&mut self.cursor, scope_id).await?;
             Ok(())
         }
     }
@@ -0,0 +1,75 @@
+//what the integration tests share besides the network: raw offline datagrams
+//and waiting for the events `recv` reports
+use raknet::{packets::*, Client, RaknetEvent, Server, Transport};
+use std::{future::Future, time::Duration};
+use tokio::time::Instant;
+
+//`open` only asks servers with a free slot to answer
+pub fn unconnected_ping(open: bool, guid: u64) -> Vec<u8> {
+    match open {
+        true => encode(UnconnectedPingOpenConnections::new(1, guid)),
+        false => encode(UnconnectedPing::new(1, guid)),
+    }
+}
+
+//an offline message as a host without a guid of its own sends it
+pub fn out_of_band(data: &[u8]) -> Vec<u8> {
+    encode(OutOfBand::new(0, data.to_vec()))
+}
+
+//both ends report their events through `recv`
+pub trait Events {
+    fn events(&self) -> impl Future<Output = Vec<RaknetEvent>>;
+}
+
+impl<T: Transport> Events for Client<T> {
+    async fn events(&self) -> Vec<RaknetEvent> {
+        self.recv().await.unwrap()
+    }
+}
+
+impl<T: Transport> Events for Server<T> {
+    async fn events(&self) -> Vec<RaknetEvent> {
+        self.recv().await.unwrap()
+    }
+}
+
+//polls `recv` until `pick` takes an event, None once `timeout` passed
+pub async fn wait_event<R>(
+    endpoint: &impl Events,
+    timeout: Duration,
+    mut pick: impl FnMut(RaknetEvent) -> Option<R>,
+) -> Option<R> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        for event in endpoint.events().await {
+            if let Some(picked) = pick(event) {
+                return Some(picked);
+            }
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+//polls `done` until it holds or `timeout` passed
+pub async fn wait_until(timeout: Duration, mut done: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    true
+}
+
+pub async fn wait_connected<T: Transport>(client: &Client<T>) {
+    let connected = wait_event(client, Duration::from_secs(30), |event| match event {
+        RaknetEvent::Connected(..) => Some(()),
+        _ => None,
+    });
+    connected.await.expect("the client did not connect");
+}
@@ -0,0 +1,250 @@
+//in-memory network for the integration tests, endpoints are `Transport`s and every datagram
+//between them can be lost, duplicated, reordered, delayed and rate limited
+//
+//every decision comes from one seeded rng in one task, so a seed replays the same fate
+//for the same sequence of datagrams
+pub mod helpers;
+
+use raknet::Transport;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    io::{Error, ErrorKind, Result},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Conditions {
+    pub loss: f64,
+    pub duplicate: f64,
+    //held back long enough for later datagrams to overtake it
+    pub reorder: f64,
+    pub latency: Duration,
+    //uniformly added on top of the latency
+    pub jitter: Duration,
+    //bytes per second each endpoint can send, unlimited when None
+    pub bandwidth: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct Counters {
+    pub delivered: AtomicU64,
+    pub lost: AtomicU64,
+    pub duplicated: AtomicU64,
+    pub reordered: AtomicU64,
+}
+
+#[derive(Clone)]
+struct Datagram {
+    from: SocketAddr,
+    to: SocketAddr,
+    data: Vec<u8>,
+}
+
+type Inbox = mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>;
+
+struct Shared {
+    conditions: Mutex<Conditions>,
+    counters: Counters,
+    endpoints: Mutex<HashMap<SocketAddr, Inbox>>,
+    sender: mpsc::UnboundedSender<Datagram>,
+}
+
+pub struct Network {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl Network {
+    pub fn new(conditions: Conditions, seed: u64) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            conditions: Mutex::new(conditions),
+            counters: Counters::default(),
+            endpoints: Mutex::new(HashMap::new()),
+            sender,
+        });
+        let router = Router {
+            shared: shared.clone(),
+            rng: StdRng::seed_from_u64(seed),
+            busy_until: HashMap::new(),
+            queue: BinaryHeap::new(),
+            pending: HashMap::new(),
+            next_id: 0,
+        };
+        let task = tokio::spawn(router.run(receiver));
+        Self { shared, task }
+    }
+
+    //the address is released again when the endpoint is dropped
+    pub fn bind(&self, address: &str) -> Result<Endpoint> {
+        let address: SocketAddr = address
+            .parse()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut endpoints = self.shared.endpoints.lock().unwrap();
+        if endpoints.contains_key(&address) {
+            return Err(Error::new(ErrorKind::AddrInUse, "address in use"));
+        }
+        endpoints.insert(address, sender);
+        Ok(Endpoint {
+            address,
+            shared: self.shared.clone(),
+            inbox: tokio::sync::Mutex::new(receiver),
+        })
+    }
+
+    //takes effect for datagrams sent afterwards
+    pub fn set_conditions(&self, conditions: Conditions) {
+        *self.shared.conditions.lock().unwrap() = conditions;
+    }
+
+    pub fn counters(&self) -> &Counters {
+        &self.shared.counters
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub struct Endpoint {
+    address: SocketAddr,
+    shared: Arc<Shared>,
+    inbox: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>>,
+}
+
+impl Transport for Endpoint {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize> {
+        let datagram = Datagram {
+            from: self.address,
+            to: target,
+            data: buf.to_vec(),
+        };
+        self.shared
+            .sender
+            .send(datagram)
+            .map_err(|_| Error::new(ErrorKind::NotConnected, "network is gone"))?;
+        Ok(buf.len())
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        match self.inbox.lock().await.recv().await {
+            Some((from, data)) => {
+                let size = data.len().min(buf.len());
+                buf[..size].copy_from_slice(&data[..size]);
+                Ok((size, from))
+            }
+            None => Err(Error::new(ErrorKind::NotConnected, "network is gone")),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.address)
+    }
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.shared.endpoints.lock().unwrap().remove(&self.address);
+    }
+}
+
+struct Router {
+    shared: Arc<Shared>,
+    rng: StdRng,
+    //each sender's link is busy with earlier datagrams until then
+    busy_until: HashMap<SocketAddr, Instant>,
+    //ids break ties so datagrams due at the same instant keep their order
+    queue: BinaryHeap<Reverse<(Instant, u64)>>,
+    pending: HashMap<u64, Datagram>,
+    next_id: u64,
+}
+
+impl Router {
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Datagram>) {
+        loop {
+            let next = self.queue.peek().map(|Reverse((at, _))| *at);
+            tokio::select! {
+                datagram = receiver.recv() => match datagram {
+                    Some(datagram) => self.schedule(datagram),
+                    None => return,
+                },
+                _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    self.deliver();
+                }
+            }
+        }
+    }
+
+    fn schedule(&mut self, datagram: Datagram) {
+        let conditions = *self.shared.conditions.lock().unwrap();
+        let counters = &self.shared.counters;
+        if self.rng.gen_bool(conditions.loss) {
+            counters.lost.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let copies = if self.rng.gen_bool(conditions.duplicate) {
+            counters.duplicated.fetch_add(1, Ordering::Relaxed);
+            2
+        } else {
+            1
+        };
+        let now = Instant::now();
+        for _ in 0..copies {
+            let mut at = now;
+            if let Some(bandwidth) = conditions.bandwidth {
+                let busy_until = self.busy_until.entry(datagram.from).or_insert(now);
+                let nanos = datagram.data.len() as u64 * 1_000_000_000 / bandwidth.max(1);
+                *busy_until = (*busy_until).max(now) + Duration::from_nanos(nanos);
+                at = *busy_until;
+            }
+            at += conditions.latency;
+            if conditions.jitter > Duration::from_millis(0) {
+                let jitter = conditions.jitter.as_micros() as u64;
+                at += Duration::from_micros(self.rng.gen_range(0, jitter + 1));
+            }
+            if self.rng.gen_bool(conditions.reorder) {
+                counters.reordered.fetch_add(1, Ordering::Relaxed);
+                at += conditions.latency + conditions.jitter + Duration::from_millis(10);
+            }
+            let id = self.next_id;
+            self.next_id += 1;
+            self.queue.push(Reverse((at, id)));
+            self.pending.insert(id, datagram.clone());
+        }
+    }
+
+    //datagrams for addresses nobody is bound to vanish like with udp
+    fn deliver(&mut self) {
+        let now = Instant::now();
+        let endpoints = self.shared.endpoints.lock().unwrap();
+        while let Some(Reverse((at, id))) = self.queue.peek().copied() {
+            if at > now {
+                break;
+            }
+            self.queue.pop();
+            let datagram = match self.pending.remove(&id) {
+                Some(datagram) => datagram,
+                None => continue,
+            };
+            if let Some(inbox) = endpoints.get(&datagram.to) {
+                if inbox.send((datagram.from, datagram.data)).is_ok() {
+                    self.shared
+                        .counters
+                        .delivered
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use raknet::{packets::*, reader::Reader, writer::Writer};
+use raknet::{codec::Decoder, packets::*};
 
 const UNCONNECTED_PING_DATA: [u8; 33] = [
     0x01, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x3d, 0x64, 0x94, 0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe,
@@ -230,131 +230,107 @@ const ALREADY_CONNECTED_DATA: [u8; 25] = [
     0x78, 0x91, 0x1b, 0x13, 0x5d, 0x5f, 0x63, 0x9d, 0x1f,
 ];
 
-#[tokio::test]
-async fn raknet_packet() {
-    let unconnected_ping = decode::<UnconnectedPing>(&UNCONNECTED_PING_DATA)
-        .await
-        .unwrap();
-    let unconnected_ping_encoded = encode::<UnconnectedPing>(unconnected_ping).await.unwrap();
+const CONNECTION_BANNED_DATA: [u8; 25] = [
+    0x17, 0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56,
+    0x78, 0x91, 0x1b, 0x13, 0x5d, 0x5f, 0x63, 0x9d, 0x1f,
+];
+
+#[test]
+fn raknet_packet() {
+    let unconnected_ping = decode::<UnconnectedPing>(&UNCONNECTED_PING_DATA).unwrap();
+    let unconnected_ping_encoded = encode::<UnconnectedPing>(unconnected_ping);
     debug_assert_eq!(&unconnected_ping_encoded, &UNCONNECTED_PING_DATA);
 
-    let unconnected_pong = decode::<UnconnectedPong>(&UNCONNECTED_PONG_DATA)
-        .await
-        .unwrap();
-    let unconnected_pong_encoded = encode::<UnconnectedPong>(unconnected_pong).await.unwrap();
+    let unconnected_pong = decode::<UnconnectedPong>(&UNCONNECTED_PONG_DATA).unwrap();
+    let unconnected_pong_encoded = encode::<UnconnectedPong>(unconnected_pong);
     debug_assert_eq!(&unconnected_pong_encoded, &UNCONNECTED_PONG_DATA);
 
-    let open_connection_request1 = decode::<OpenConnectionRequest1>(&OPEN_CONNECTION_REQUEST1_DATA)
-        .await
-        .unwrap();
+    let open_connection_request1 =
+        decode::<OpenConnectionRequest1>(&OPEN_CONNECTION_REQUEST1_DATA).unwrap();
     let open_connection_request1_encoded =
-        encode::<OpenConnectionRequest1>(open_connection_request1)
-            .await
-            .unwrap();
+        encode::<OpenConnectionRequest1>(open_connection_request1);
     debug_assert_eq!(
         &open_connection_request1_encoded,
         &OPEN_CONNECTION_REQUEST1_DATA
     );
 
-    let open_connection_request2 = decode::<OpenConnectionRequest2>(&OPEN_CONNECTION_REQUEST2_DATA)
-        .await
-        .unwrap();
+    let open_connection_request2 =
+        decode::<OpenConnectionRequest2>(&OPEN_CONNECTION_REQUEST2_DATA).unwrap();
     let open_connection_request2_encoded =
-        encode::<OpenConnectionRequest2>(open_connection_request2)
-            .await
-            .unwrap();
+        encode::<OpenConnectionRequest2>(open_connection_request2);
     debug_assert_eq!(
         &open_connection_request2_encoded,
         &OPEN_CONNECTION_REQUEST2_DATA
     );
 
-    let open_connection_reply1 = decode::<OpenConnectionReply1>(&OPEN_CONNECTION_REPLY1_DATA)
-        .await
-        .unwrap();
-    let open_connection_reply1_encoded = encode::<OpenConnectionReply1>(open_connection_reply1)
-        .await
-        .unwrap();
+    let open_connection_reply1 =
+        decode::<OpenConnectionReply1>(&OPEN_CONNECTION_REPLY1_DATA).unwrap();
+    let open_connection_reply1_encoded = encode::<OpenConnectionReply1>(open_connection_reply1);
     debug_assert_eq!(
         &open_connection_reply1_encoded,
         &OPEN_CONNECTION_REPLY1_DATA
     );
 
-    let open_connection_reply2 = decode::<OpenConnectionReply2>(&OPEN_CONNECTION_REPLY2_DATA)
-        .await
-        .unwrap();
-    let open_connection_reply2_encoded = encode::<OpenConnectionReply2>(open_connection_reply2)
-        .await
-        .unwrap();
+    let open_connection_reply2 =
+        decode::<OpenConnectionReply2>(&OPEN_CONNECTION_REPLY2_DATA).unwrap();
+    let open_connection_reply2_encoded = encode::<OpenConnectionReply2>(open_connection_reply2);
     debug_assert_eq!(
         &open_connection_reply2_encoded,
         &OPEN_CONNECTION_REPLY2_DATA
     );
 
-    let connection_request = decode::<ConnectionRequest>(&CONNECTION_REQUEST_DATA)
-        .await
-        .unwrap();
-    let connection_request_encoded = encode::<ConnectionRequest>(connection_request)
-        .await
-        .unwrap();
+    let connection_request = decode::<ConnectionRequest>(&CONNECTION_REQUEST_DATA).unwrap();
+    let connection_request_encoded = encode::<ConnectionRequest>(connection_request);
     debug_assert_eq!(&connection_request_encoded, &CONNECTION_REQUEST_DATA);
 
     let connection_request_accepted =
-        decode::<ConnectionRequestAccepted>(&CONNECTION_REQUEST_ACCEPTED_DATA)
-            .await
-            .unwrap();
-    encode::<ConnectionRequestAccepted>(connection_request_accepted)
-        .await
-        .unwrap();
-
-    let new_incoming_connection = decode::<NewIncomingConnection>(&NEW_INCOMING_CONNECTION_DATA)
-        .await
-        .unwrap();
-    encode::<NewIncomingConnection>(new_incoming_connection)
-        .await
-        .unwrap();
+        decode::<ConnectionRequestAccepted>(&CONNECTION_REQUEST_ACCEPTED_DATA).unwrap();
+    encode::<ConnectionRequestAccepted>(connection_request_accepted);
+
+    let new_incoming_connection =
+        decode::<NewIncomingConnection>(&NEW_INCOMING_CONNECTION_DATA).unwrap();
+    encode::<NewIncomingConnection>(new_incoming_connection);
 
     let incompatible_protocol_version =
-        decode::<IncompatibleProtocolVersion>(&INCOMPATIBLE_PROTOCOL_VERSION_DATA)
-            .await
-            .unwrap();
-    encode::<IncompatibleProtocolVersion>(incompatible_protocol_version)
-        .await
-        .unwrap();
-
-    let ack = decode::<Ack>(&ACK_DATA).await.unwrap();
-    let ack_encoded = encode::<Ack>(ack).await.unwrap();
+        decode::<IncompatibleProtocolVersion>(&INCOMPATIBLE_PROTOCOL_VERSION_DATA).unwrap();
+    encode::<IncompatibleProtocolVersion>(incompatible_protocol_version);
+
+    let ack = decode::<Ack>(&ACK_DATA).unwrap();
+    let ack_encoded = encode::<Ack>(ack);
     debug_assert_eq!(&ack_encoded, &ACK_DATA);
 
-    let ack2 = decode::<Ack>(&ACK_DATA2).await.unwrap();
-    let ack2_encoded = encode::<Ack>(ack2).await.unwrap();
+    let ack2 = decode::<Ack>(&ACK_DATA2).unwrap();
+    let ack2_encoded = encode::<Ack>(ack2);
     debug_assert_eq!(&ack2_encoded, &ACK_DATA2);
 
-    let nack = decode::<Nack>(&NACK_DATA).await.unwrap();
-    let nack_encoded = encode::<Nack>(nack).await.unwrap();
+    let nack = decode::<Nack>(&NACK_DATA).unwrap();
+    let nack_encoded = encode::<Nack>(nack);
     debug_assert_eq!(&nack_encoded, &NACK_DATA);
 
-    let nack2 = decode::<Nack>(&NACK_DATA2).await.unwrap();
-    let nack2_encoded = encode::<Nack>(nack2).await.unwrap();
+    let nack2 = decode::<Nack>(&NACK_DATA2).unwrap();
+    let nack2_encoded = encode::<Nack>(nack2);
     debug_assert_eq!(&nack2_encoded, &NACK_DATA2);
 
-    let connected_ping = decode::<ConnectedPing>(&CONNECTEDPING_DATA).await.unwrap();
-    let connected_ping_encoded = encode::<ConnectedPing>(connected_ping).await.unwrap();
+    let connected_ping = decode::<ConnectedPing>(&CONNECTEDPING_DATA).unwrap();
+    let connected_ping_encoded = encode::<ConnectedPing>(connected_ping);
     debug_assert_eq!(&connected_ping_encoded, &CONNECTEDPING_DATA);
 
-    let connected_pong = decode::<ConnectedPong>(&CONNECTEDPONG_DATA).await.unwrap();
-    let connected_pong_encoded = encode::<ConnectedPong>(connected_pong).await.unwrap();
+    let connected_pong = decode::<ConnectedPong>(&CONNECTEDPONG_DATA).unwrap();
+    let connected_pong_encoded = encode::<ConnectedPong>(connected_pong);
     debug_assert_eq!(&connected_pong_encoded, &CONNECTEDPONG_DATA);
 
-    let frameset = FrameSet::decode(&FRAME_SETPACKET_DATA).await.unwrap();
-    let frameset_encoded = frameset.encode().await.unwrap();
+    let frameset = FrameSet::decode(&FRAME_SETPACKET_DATA).unwrap();
+    let frameset_encoded = frameset.encode();
     debug_assert_eq!(&frameset_encoded, &FRAME_SETPACKET_DATA);
 
-    let already_connected = decode::<AlreadyConnected>(&ALREADY_CONNECTED_DATA)
-        .await
-        .unwrap();
-    let already_connected_encoded = encode::<AlreadyConnected>(already_connected).await.unwrap();
+    let already_connected = decode::<AlreadyConnected>(&ALREADY_CONNECTED_DATA).unwrap();
+    let already_connected_encoded = encode::<AlreadyConnected>(already_connected);
     debug_assert_eq!(&already_connected_encoded, &ALREADY_CONNECTED_DATA);
 
+    let connection_banned = decode::<ConnectionBanned>(&CONNECTION_BANNED_DATA).unwrap();
+    let connection_banned_encoded = encode::<ConnectionBanned>(connection_banned);
+    debug_assert_eq!(&connection_banned_encoded, &CONNECTION_BANNED_DATA);
+
     let nack = Nack::new((0, 1));
     debug_assert_eq!(nack.get_all(), vec![0, 1]);
 
@@ -365,35 +341,82 @@ async fn raknet_packet() {
     let _incompatible_protocol_version = IncompatibleProtocolVersion::new(0x0, 0x0);
 
     let sequenced_frame = Frame::new(Reliability::ReliableSequenced, b"test");
-    let mut frame_buff = Writer::new(vec![]);
-    sequenced_frame.encode(&mut frame_buff).await.unwrap();
-    let buff = frame_buff.get_raw_payload();
-    let mut reader = Reader::new(&buff);
-    let _sequenced_frame_decoded = Frame::decode(&mut reader).await.unwrap();
-
-    let disconnected = decode::<Disconnected>(&[0x15]).await.unwrap();
-    debug_assert_eq!(&encode(disconnected).await.unwrap(), &[0x15]);
+    let mut buff = vec![];
+    sequenced_frame.encode_into(&mut buff);
+    let mut decoder = Decoder::new(&buff);
+    let sequenced_frame_decoded = Frame::decode(&mut decoder).unwrap();
+    //the body is borrowed from the datagram
+    assert!(matches!(
+        sequenced_frame_decoded.data,
+        std::borrow::Cow::Borrowed(b"test")
+    ));
+
+    let disconnected = decode::<Disconnected>(&[0x15]).unwrap();
+    debug_assert_eq!(&encode(disconnected), &[0x15]);
 }
 
-async fn get_encoded_len(f: Frame) -> usize {
-    let mut data = Writer::new(vec![]);
-    f.encode(&mut data).await.unwrap();
-    data.get_raw_payload().len()
+fn get_encoded_len(f: Frame<'_>) -> usize {
+    let mut data = vec![];
+    f.encode_into(&mut data);
+    data.len()
 }
 
-#[tokio::test]
-async fn frame() {
+#[test]
+fn frame() {
     let frame = Frame::new(Reliability::Unreliable, &[0u8; 10]);
-    debug_assert_eq!(frame.length(), get_encoded_len(frame).await);
+    debug_assert_eq!(frame.length(), get_encoded_len(frame));
     let frame = Frame::new(Reliability::UnreliableSequenced, &[0u8; 10]);
-    debug_assert_eq!(frame.length(), get_encoded_len(frame).await);
+    debug_assert_eq!(frame.length(), get_encoded_len(frame));
     let frame = Frame::new(Reliability::Reliable, &[0u8; 10]);
-    debug_assert_eq!(frame.length(), get_encoded_len(frame).await);
+    debug_assert_eq!(frame.length(), get_encoded_len(frame));
     let frame = Frame::new(Reliability::ReliableOrdered, &[0u8; 10]);
-    debug_assert_eq!(frame.length(), get_encoded_len(frame).await);
+    debug_assert_eq!(frame.length(), get_encoded_len(frame));
     let frame = Frame::new(Reliability::ReliableSequenced, &[0u8; 10]);
-    debug_assert_eq!(frame.length(), get_encoded_len(frame).await);
+    debug_assert_eq!(frame.length(), get_encoded_len(frame));
 }
 
 #[test]
 fn event_error() {}
+
+#[test]
+fn out_of_band() {
+    let encoded = encode(OutOfBand::new(0x0102030405060708, vec![0xaa, 0xbb]));
+    assert_eq!(encoded[0], 0x0d);
+    assert_eq!(&encoded[1..9], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(&encoded[9..25], &MAGIC);
+    let decoded = decode::<OutOfBand>(&encoded).unwrap();
+    assert_eq!(decoded.guid, 0x0102030405060708);
+    assert_eq!(decoded.data, [0xaa, 0xbb]);
+
+    let mut stray = encoded.clone();
+    stray[9] = 0x1;
+    assert!(decode::<OutOfBand>(&stray).is_err());
+}
+
+#[test]
+fn open_connections_ping() {
+    let mut data = UNCONNECTED_PING_DATA;
+    data[0] = 0x02;
+    let ping = decode::<UnconnectedPingOpenConnections>(&data).unwrap();
+    assert_eq!(ping.time, 0x0f3d6494);
+    assert_eq!(ping.guid, 0x8ce5bf6932f87a55);
+    assert_eq!(encode(ping), data);
+}
+
+#[test]
+fn advertise_system() {
+    let mut data = UNCONNECTED_PONG_DATA;
+    data[0] = 0x1d;
+    let advertisement = decode::<AdvertiseSystem>(&data).unwrap();
+    assert!(advertisement.data.starts_with("MCPE;Dedicated Server;"));
+    assert_eq!(encode(advertisement), data);
+}
+
+#[test]
+fn no_free_incoming_connections() {
+    let encoded = encode(NoFreeIncomingConnections::new(0x1122334455667788));
+    assert_eq!(encoded[0], 0x14);
+    assert_eq!(&encoded[1..17], &MAGIC);
+    let decoded = decode::<NoFreeIncomingConnections>(&encoded).unwrap();
+    assert_eq!(decoded.guid, 0x1122334455667788);
+}
@@ -1,19 +1,61 @@
+use raknet::packets::{
+    encode, OpenConnectionReply2, OpenConnectionRequest2, Packet, UnconnectedPong,
+};
 use raknet::reader::{Endian, Reader};
 use raknet::writer::Writer;
-use raknet::{Client, Ping, RaknetEvent, Server};
+use raknet::{
+    Client, DisconnectReason, Ping, PingConfig, QueuedMessages, RaknetError, RaknetEvent,
+    ReconnectPolicy, Server, Transport,
+};
 use std::cmp::Ordering;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::Ordering::Relaxed;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::helpers::{out_of_band, unconnected_ping, wait_connected, wait_event, wait_until};
+use common::{Conditions, Endpoint, Network};
+
+const SERVER: &str = "10.0.0.1:19132";
+
+const CLIENT: &str = "10.0.0.2:50000";
 
 #[tokio::test]
 async fn ping() {
-    let pinger = Ping::new().await;
-    let remote = "mco.mineplex.com:19132"
-        .to_socket_addrs()
-        .unwrap()
-        .next()
+    let conditions = Conditions {
+        latency: Duration::from_millis(20),
+        jitter: Duration::from_millis(5),
+        ..Conditions::default()
+    };
+    let network = Network::new(conditions, 1);
+    let server = simulated_server(&network).await;
+
+    let pinger = Ping::with_transport(network.bind(CLIENT).unwrap());
+    let pong = pinger.ping(server.local_addr).await.unwrap();
+    assert_eq!(pong.motd, "MCPE;raknet rs;");
+    assert_eq!(pong.guid, server.id);
+    assert_eq!(pong.address, server.local_addr);
+    assert!(pong.rtt >= Duration::from_millis(40));
+
+    //a matching pong from another address is not the answer
+    let target = network.bind("10.0.0.4:19132").unwrap();
+    let stranger = network.bind("10.0.0.3:50000").unwrap();
+    let ping = tokio::spawn(async move { pinger.ping("10.0.0.4:19132".parse().unwrap()).await });
+    let mut buf = [0u8; 1500];
+    target.recv_from(&mut buf).await.unwrap();
+    assert_ne!(&buf[1..9], &[0; 8]);
+    assert_ne!(&buf[25..33], &[0; 8]);
+    let mut pong = vec![0x1c];
+    pong.extend_from_slice(&buf[1..9]);
+    pong.extend_from_slice(&1u64.to_be_bytes());
+    pong.extend_from_slice(&raknet::packets::MAGIC);
+    pong.extend_from_slice(&[0, 0]);
+    stranger
+        .send_to(&pong, CLIENT.parse().unwrap())
+        .await
         .unwrap();
-    let pong = pinger.ping(remote).await.unwrap();
-    println!("{}", pong);
+    assert!(ping.await.unwrap().is_err());
 }
 
 const BUFFER: [u8; 8] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7];
@@ -44,6 +86,8 @@ async fn reader_writer() {
     cursor.write_i64(TEST_I64, Endian::Little).await.unwrap();
 
     cursor.write_address(test_address).await.unwrap();
+    let test_address_v6: SocketAddr = "[2001:db8::1]:19132".parse().unwrap();
+    cursor.write_address(test_address_v6).await.unwrap();
     cursor.write_magic().await.unwrap();
     cursor.write_string(test_string).await.unwrap();
 
@@ -66,7 +110,8 @@ async fn reader_writer() {
     assert_eq!(cursor.read_i64(Endian::Little).await.unwrap(), TEST_I64);
 
     assert_eq!(cursor.read_address().await.unwrap(), test_address);
-    assert_eq!(cursor.read_magic().await.unwrap(), true);
+    assert_eq!(cursor.read_address().await.unwrap(), test_address_v6);
+    assert!(cursor.read_magic().await.unwrap());
     assert_eq!(
         cursor.read_string().await.unwrap().cmp(test_string),
         Ordering::Equal
@@ -75,13 +120,14 @@ async fn reader_writer() {
 
 #[tokio::test]
 async fn server_test() {
+    let local: SocketAddr = "127.0.0.1:0".parse().expect("could not parse addr");
+    let mut server = Server::new(
+            local,
+        "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()
+        );
+    server.listen().await.unwrap();
+    let remote = server.local_addr;
     tokio::spawn(async move {
-        let local: SocketAddr = "127.0.0.1:19132".parse().expect("could not parse addr");
-        let mut server = Server::new(
-                local,
-            "MCPE;§5raknet rs;390;1.17.42;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;".to_owned()
-            );
-        server.listen().await.unwrap();
         loop {
             tokio::time::sleep(std::time::Duration::from_millis(50)).await;
             let events = server.recv().await.unwrap();
@@ -111,10 +157,9 @@ async fn server_test() {
             }
         }
     });
-    let pinger = Ping::new().await;
-    let remote = "127.0.0.1:19132".to_socket_addrs().unwrap().next().unwrap();
+    let pinger = Ping::new().await.unwrap();
     let pong = pinger.ping(remote).await.unwrap();
-    println!("{}", pong);
+    println!("{}", pong.motd);
 
     let mut client = Client::new(remote, true).await.unwrap();
     client.connect().await.unwrap();
@@ -134,12 +179,10 @@ async fn server_test() {
                     dissconnected = true;
                     break;
                 }
-                RaknetEvent::Packet(packet) => {
-                    if packet.data[0] == 0x48 {
-                        let msg = String::from_utf8_lossy(&packet.data);
-                        if msg == "Hello Client!!" {
-                            client.send(&[0xffu8; 4896]).await.unwrap();
-                        }
+                RaknetEvent::Packet(packet) if packet.data[0] == 0x48 => {
+                    let msg = String::from_utf8_lossy(&packet.data);
+                    if msg == "Hello Client!!" {
+                        client.send(&[0xffu8; 4896]).await.unwrap();
                     }
                 }
                 _ => {}
@@ -150,3 +193,1066 @@ async fn server_test() {
         }
     }
 }
+
+#[tokio::test]
+async fn accept_test() {
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let local = server.local_addr;
+    tokio::spawn(async move {
+        loop {
+            let mut peer = server.accept().await.unwrap();
+            tokio::spawn(async move {
+                while let Some(event) = peer.recv().await {
+                    if let RaknetEvent::Packet(packet) = event {
+                        peer.send(&packet.data).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    let mut client = Client::new(local, true).await.unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    loop {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut echoed = false;
+        for event in client.recv().await.unwrap() {
+            match event {
+                RaknetEvent::Connected(..) => {
+                    client.send(b"Hello Peer!!").await.unwrap();
+                }
+                RaknetEvent::Packet(packet) => {
+                    assert_eq!(&packet.data, b"Hello Peer!!");
+                    echoed = true;
+                }
+                _ => {}
+            }
+        }
+        if echoed {
+            break;
+        }
+    }
+    let stats = client.stats().await.unwrap();
+    assert!(stats.datagrams_sent > 0 && stats.bytes_sent > 0);
+    assert!(stats.datagrams_received > 0 && stats.bytes_received > 0);
+    assert_eq!(stats.split_reassemblies, 0);
+
+    let rtt = client.ping().await.unwrap();
+    let latency = client.latency().await.unwrap();
+    assert!(latency.min <= rtt && rtt <= latency.max);
+
+    //both ends share the same clock
+    let clock = client.remote_clock().await.unwrap();
+    assert!(clock.offset.abs() <= clock.rtt as i64 + 1);
+}
+
+#[tokio::test]
+async fn shutdown_test() {
+    //every round binds the port the first one got, so it must have been released
+    let mut local: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    for _ in 0..3 {
+        let mut server = Server::new(local, "MCPE;raknet rs;".to_owned());
+        server.listen().await.unwrap();
+        local = server.local_addr;
+
+        let mut client = Client::new(local, true).await.unwrap();
+        client.connect().await.unwrap();
+        client.listen().await;
+        let mut peer = server.accept().await.unwrap();
+
+        server.shutdown().await;
+        loop {
+            match peer.recv().await {
+                Some(RaknetEvent::Disconnected(_, _, DisconnectReason::Shutdown)) => break,
+                Some(_) => {}
+                None => panic!("peer closed without a Disconnected event"),
+            }
+        }
+
+        //the server told the client before releasing the port
+        let mut disconnected = false;
+        for event in client.recv().await.unwrap() {
+            if let RaknetEvent::Disconnected(..) = event {
+                disconnected = true;
+            }
+        }
+        assert!(disconnected);
+        client.shutdown().await;
+    }
+}
+
+#[tokio::test]
+async fn shutdown_full_queue_test() {
+    let network = Network::new(Conditions::default(), 33);
+    let mut server = simulated_server(&network).await;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    //the server never reads, its peer's queue of 64 events fills up and blocks the driver
+    for i in 0..100u8 {
+        client.send(&[0xfe, i]).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let start = Instant::now();
+    server.shutdown().await;
+    assert!(start.elapsed() < Duration::from_secs(1));
+    let disconnected = wait_event(&client, Duration::from_secs(2), |event| match event {
+        RaknetEvent::Disconnected(_, _, reason) => Some(reason),
+        _ => None,
+    });
+    let disconnected = disconnected.await.unwrap();
+    assert_eq!(disconnected, DisconnectReason::ClosedByPeer);
+
+    //no driver is left holding the socket
+    drop(server);
+    network.bind(SERVER).unwrap();
+}
+
+async fn next_disconnect(client: &Client) -> (u64, DisconnectReason) {
+    loop {
+        for event in client.recv().await.unwrap() {
+            if let RaknetEvent::Disconnected(_, guid, reason) = event {
+                return (guid, reason);
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn disconnect_reason_test() {
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let local = server.local_addr;
+
+    //closed by the client
+    let mut client = Client::new(local, true).await.unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    let mut peer = server.accept().await.unwrap();
+    assert_eq!(peer.guid(), client.guid);
+    client.disconnect().await;
+    assert_eq!(
+        next_disconnect(&client).await,
+        (server.id, DisconnectReason::Closed)
+    );
+    match peer.recv().await {
+        Some(RaknetEvent::Disconnected(_, guid, reason)) => {
+            assert_eq!(guid, client.guid);
+            assert_eq!(reason, DisconnectReason::ClosedByPeer);
+        }
+        _ => panic!("expected Disconnected"),
+    }
+    assert!(peer.recv().await.is_none());
+    client.shutdown().await;
+
+    //kicked
+    let mut client = Client::new(local, true).await.unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    let mut peer = server.accept().await.unwrap();
+    server.kick(peer.remote_addr()).await;
+    match peer.recv().await {
+        Some(RaknetEvent::Disconnected(_, _, reason)) => {
+            assert_eq!(reason, DisconnectReason::Kicked)
+        }
+        _ => panic!("expected Disconnected"),
+    }
+    assert_eq!(
+        next_disconnect(&client).await,
+        (server.id, DisconnectReason::ClosedByPeer)
+    );
+    client.shutdown().await;
+
+    //banned
+    let mut client = Client::new(local, true).await.unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    let peer = server.accept().await.unwrap();
+    server.ban(peer.remote_addr()).await;
+    assert_eq!(
+        next_disconnect(&client).await,
+        (server.id, DisconnectReason::ClosedByPeer)
+    );
+    client.shutdown().await;
+    let mut client = Client::new(local, true).await.unwrap();
+    assert!(matches!(
+        client.connect().await,
+        Err(RaknetError::ConnectionBanned(_))
+    ));
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn order_test() {
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let local = server.local_addr;
+    let received = tokio::spawn(async move {
+        let mut peer = server.accept().await.unwrap();
+        let mut received = vec![];
+        while received.len() < 500 {
+            if let Some(RaknetEvent::Packet(packet)) = peer.recv().await {
+                received.push(u32::from_be_bytes(packet.data[1..5].try_into().unwrap()));
+            }
+        }
+        (received, peer.stats().await)
+    });
+
+    let mut client = Client::new(local, true).await.unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+    for i in 0..500u32 {
+        let mut data = vec![0xfe];
+        data.extend_from_slice(&i.to_be_bytes());
+        data.resize(100, 0);
+        client.send(&data).await.unwrap();
+    }
+
+    let (received, stats) = received.await.unwrap();
+    assert_eq!(received, (0..500).collect::<Vec<_>>());
+    //nothing is lost on loopback, a nack would mean datagrams were handled out of order
+    assert_eq!(stats.nacks_sent, 0);
+}
+
+async fn simulated_server(network: &Network) -> Server<Endpoint> {
+    let transport = network.bind(SERVER).unwrap();
+    let mut server = Server::with_transport(transport, "MCPE;raknet rs;".to_owned()).unwrap();
+    server.listen().await.unwrap();
+    server
+}
+
+//sends the messages once connected and returns what the server's peer received
+async fn transfer(
+    network: &Network,
+    server: Server<Endpoint>,
+    messages: &[Vec<u8>],
+    conditions: Conditions,
+) -> Vec<Vec<u8>> {
+    let count = messages.len();
+    let received = tokio::spawn(async move {
+        let mut peer = server.accept().await.unwrap();
+        let mut received = vec![];
+        while received.len() < count {
+            match peer.recv().await {
+                Some(RaknetEvent::Packet(packet)) => received.push(packet.data),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        received
+    });
+
+    //the offline handshake is not retried, so the network only turns hostile afterwards
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+    network.set_conditions(conditions);
+    for message in messages {
+        client.send(message).await.unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(30), received)
+        .await
+        .expect("messages were not delivered")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn lossy_link_test() {
+    let network = Network::new(Conditions::default(), 7);
+    let server = simulated_server(&network).await;
+    let conditions = Conditions {
+        loss: 0.1,
+        duplicate: 0.05,
+        reorder: 0.1,
+        latency: Duration::from_millis(10),
+        jitter: Duration::from_millis(10),
+        bandwidth: None,
+    };
+
+    //every tenth message is split over several datagrams
+    let messages: Vec<Vec<u8>> = (0..200u32)
+        .map(|i| {
+            let mut data = vec![0xfe];
+            data.extend_from_slice(&i.to_be_bytes());
+            data.resize(if i % 10 == 0 { 4000 } else { 100 }, i as u8);
+            data
+        })
+        .collect();
+    let received = transfer(&network, server, &messages, conditions).await;
+    assert!(
+        received == messages,
+        "messages were lost, duplicated or reordered"
+    );
+
+    let counters = network.counters();
+    assert!(counters.lost.load(Relaxed) > 0);
+    assert!(counters.duplicated.load(Relaxed) > 0);
+    assert!(counters.reordered.load(Relaxed) > 0);
+}
+
+#[tokio::test]
+async fn bandwidth_test() {
+    let network = Network::new(Conditions::default(), 11);
+    let server = simulated_server(&network).await;
+    let conditions = Conditions {
+        latency: Duration::from_millis(5),
+        bandwidth: Some(200_000),
+        ..Conditions::default()
+    };
+
+    let messages: Vec<Vec<u8>> = (0..100u32)
+        .map(|i| {
+            let mut data = vec![0xfe];
+            data.extend_from_slice(&i.to_be_bytes());
+            data.resize(1000, 0);
+            data
+        })
+        .collect();
+    let start = Instant::now();
+    let received = transfer(&network, server, &messages, conditions).await;
+    assert!(received == messages);
+    //100kB at 200kB/s
+    assert!(start.elapsed() >= Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn connect_host_test() {
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let port = server.local_addr.port();
+
+    let mut client = Client::connect_host(&format!("localhost:{}", port))
+        .await
+        .unwrap();
+    assert_eq!(client.remote, server.local_addr);
+    client.listen().await;
+    let peer = server.accept().await.unwrap();
+    assert_eq!(peer.guid(), client.guid);
+    client.shutdown().await;
+
+    //every candidate is reported with its own error
+    server.ban("127.0.0.1:1".parse().unwrap()).await;
+    match Client::connect_host(&format!("127.0.0.1:{}", port)).await {
+        Err(RaknetError::ConnectFailed(failed)) => {
+            assert_eq!(failed.len(), 1);
+            assert_eq!(failed[0].0, server.local_addr);
+            assert!(matches!(failed[0].1, RaknetError::ConnectionBanned(_)));
+        }
+        _ => panic!("expected ConnectFailed"),
+    }
+}
+
+#[tokio::test]
+async fn connect_timeout_test() {
+    let network = Network::new(Conditions::default(), 34);
+    let server = simulated_server(&network).await;
+    //takes every request and never answers
+    let silent = network.bind("10.0.0.8:19132").unwrap();
+    let address: SocketAddr = "10.0.0.8:19132".parse().unwrap();
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, address).unwrap();
+
+    let meanwhile = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let duplicate = server.connect(address).await;
+        //the dialed host's own ping still gets its pong
+        silent
+            .send_to(&unconnected_ping(false, 2), SERVER.parse().unwrap())
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1500];
+        let pong = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let (_, source) = silent.recv_from(&mut buf).await.unwrap();
+                if buf[0] == UnconnectedPong::ID {
+                    return source;
+                }
+            }
+        });
+        (duplicate, pong.await)
+    };
+    let (connected, dialed, (duplicate, pong)) =
+        tokio::join!(client.connect(), server.connect(address), meanwhile);
+    match connected {
+        Err(RaknetError::TimedOut(remote)) => assert_eq!(remote, address),
+        _ => panic!("expected TimedOut"),
+    }
+    match dialed {
+        Err(RaknetError::TimedOut(remote)) => assert_eq!(remote, address),
+        _ => panic!("expected TimedOut"),
+    }
+    //a second dial would take the replies of the first one
+    assert!(matches!(duplicate, Err(RaknetError::AlreadyConnected(_))));
+    assert_eq!(pong.unwrap(), server.local_addr);
+}
+
+#[tokio::test]
+async fn connect_request_timeout_test() {
+    let network = Network::new(Conditions::default(), 39);
+    let server = simulated_server(&network).await;
+    //accepts the offline handshake and never answers the connection request
+    let silent = network.bind("10.0.0.8:19132").unwrap();
+    let address: SocketAddr = "10.0.0.8:19132".parse().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1500];
+        silent.recv_from(&mut buf).await.unwrap();
+        let reply2 = OpenConnectionReply2::new(7, SERVER.parse().unwrap(), 1400, false);
+        silent
+            .send_to(&encode(reply2), SERVER.parse().unwrap())
+            .await
+            .unwrap();
+        while silent.recv_from(&mut buf).await.is_ok() {}
+    });
+
+    match server.connect(address).await {
+        Err(RaknetError::TimedOut(remote)) => assert_eq!(remote, address),
+        _ => panic!("expected TimedOut"),
+    }
+}
+
+//returns the client's address as the server sees it
+async fn echo_once<T: Transport>(server: &Server<T>, client: &mut Client) -> SocketAddr {
+    client.connect().await.unwrap();
+    client.listen().await;
+    let mut peer = server.accept().await.unwrap();
+    assert_eq!(peer.remote_addr().is_ipv6(), client.remote.is_ipv6());
+    wait_connected(client).await;
+    client.send(b"\xfeHello").await.unwrap();
+    loop {
+        if let Some(RaknetEvent::Packet(packet)) = peer.recv().await {
+            peer.send(&packet.data).await.unwrap();
+            break;
+        }
+    }
+    loop {
+        for event in client.recv().await.unwrap() {
+            if let RaknetEvent::Packet(packet) = event {
+                assert_eq!(packet.data, b"\xfeHello");
+                return peer.remote_addr();
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn ipv6_test() {
+    let mut server = Server::new("[::1]:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let pinger = Ping::new().await.unwrap();
+    let pong = pinger.ping(server.local_addr).await.unwrap();
+    assert_eq!(pong.motd, "MCPE;raknet rs;");
+
+    let mut client = Client::new(server.local_addr, false).await.unwrap();
+    echo_once(&server, &mut client).await;
+    client.shutdown().await;
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn dual_stack_test() {
+    let mut server = Server::dual_stack(0, "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+    let port = server.local_addr.port();
+
+    let mut client = Client::new(format!("[::1]:{}", port).parse().unwrap(), false)
+        .await
+        .unwrap();
+    echo_once(&server, &mut client).await;
+
+    //seen as an IPv4 peer, not as ::ffff:127.0.0.1
+    let mut client = Client::new(format!("127.0.0.1:{}", port).parse().unwrap(), false)
+        .await
+        .unwrap();
+    let seen = echo_once(&server, &mut client).await;
+    let mapped: SocketAddr = format!("[::ffff:127.0.0.1]:{}", seen.port())
+        .parse()
+        .unwrap();
+    assert_eq!(seen.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert!(server.stats(&mapped).await.is_some());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn bind_test() {
+    let mut server = Server::new("127.0.0.1:0".parse().unwrap(), "MCPE;raknet rs;".to_owned());
+    server.listen().await.unwrap();
+
+    let client = Client::new(server.local_addr, false).await.unwrap();
+    assert_eq!(client.local.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+    assert_ne!(client.local.port(), 0);
+
+    let mut client = Client::bind("127.0.0.1:0".parse().unwrap(), server.local_addr)
+        .await
+        .unwrap();
+    assert_ne!(client.local.port(), 0);
+    client.connect().await.unwrap();
+    client.listen().await;
+    let peer = server.accept().await.unwrap();
+    assert_eq!(peer.remote_addr(), client.local);
+
+    //the port is taken now
+    assert!(Client::bind(client.local, server.local_addr).await.is_err());
+}
+
+//cuts the link until the client times out, sends a message during the outage
+//and brings up a restarted server behind the same address
+async fn restart_during_outage(
+    network: &Network,
+    policy: ReconnectPolicy,
+) -> (Client<Endpoint>, u64, Server<Endpoint>) {
+    let server = simulated_server(network).await;
+    let old_guid = server.id;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.set_reconnect(policy);
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    network.set_conditions(Conditions {
+        loss: 1.0,
+        ..Conditions::default()
+    });
+    let reconnecting = wait_event(&client, Duration::from_secs(15), |event| match event {
+        RaknetEvent::Reconnecting(_, 1) => Some(()),
+        RaknetEvent::Disconnected(..) => panic!("the timeout was reported"),
+        _ => None,
+    });
+    reconnecting
+        .await
+        .expect("the client did not start reconnecting");
+    client.send(&[0xfe, 0x1]).await.unwrap();
+
+    drop(server);
+    let transport = loop {
+        match network.bind(SERVER) {
+            Ok(transport) => break transport,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    let mut server = Server::with_transport(transport, "MCPE;raknet rs;".to_owned()).unwrap();
+    server.listen().await.unwrap();
+    network.set_conditions(Conditions::default());
+    (client, old_guid, server)
+}
+
+async fn next_event<T: Transport>(client: &Client<T>, timeout: Duration) -> Option<RaknetEvent> {
+    wait_event(client, timeout, |event| match event {
+        RaknetEvent::Reconnecting(..) => None,
+        event => Some(event),
+    })
+    .await
+}
+
+#[tokio::test]
+async fn reconnect_test() {
+    let network = Network::new(Conditions::default(), 13);
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_millis(200),
+        max_attempts: 100,
+        attempt_timeout: Duration::from_millis(200),
+        queued: QueuedMessages::Replay,
+        ..ReconnectPolicy::default()
+    };
+    let (client, old_guid, server) = restart_during_outage(&network, policy).await;
+
+    let event = next_event(&client, Duration::from_secs(10))
+        .await
+        .expect("the client did not reconnect");
+    match event {
+        RaknetEvent::Reconnected(_, guid) => {
+            assert_eq!(guid, server.id);
+            assert_ne!(guid, old_guid);
+        }
+        _ => panic!("expected Reconnected"),
+    }
+
+    //the message of the outage is replayed
+    let mut peer = server.accept().await.unwrap();
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(RaknetEvent::Packet(packet)) = peer.recv().await {
+                return packet.data;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(received, [0xfe, 0x1]);
+}
+
+#[tokio::test]
+async fn disconnect_during_outage_test() {
+    let network = Network::new(Conditions::default(), 36);
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(200),
+        max_backoff: Duration::from_millis(200),
+        max_attempts: 100,
+        attempt_timeout: Duration::from_millis(200),
+        ..ReconnectPolicy::default()
+    };
+    let (mut client, old_guid, server) = restart_during_outage(&network, policy).await;
+    client.disconnect().await;
+
+    let event = next_event(&client, Duration::from_secs(5))
+        .await
+        .expect("the disconnect was not reported");
+    match event {
+        RaknetEvent::Disconnected(_, guid, reason) => {
+            assert_eq!(guid, old_guid);
+            assert_eq!(reason, DisconnectReason::Closed);
+        }
+        _ => panic!("expected Disconnected"),
+    }
+
+    //no attempt brings the connection back afterwards
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    for event in client.recv().await.unwrap() {
+        assert!(
+            !matches!(
+                event,
+                RaknetEvent::Reconnecting(..) | RaknetEvent::Reconnected(..)
+            ),
+            "the client kept reconnecting"
+        );
+    }
+    assert!(server.recv().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reconnect_same_server_test() {
+    let network = Network::new(Conditions::default(), 17);
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(50),
+        max_backoff: Duration::from_millis(200),
+        max_attempts: 100,
+        attempt_timeout: Duration::from_millis(200),
+        same_server: true,
+        ..ReconnectPolicy::default()
+    };
+    let (client, old_guid, _server) = restart_during_outage(&network, policy).await;
+
+    let event = next_event(&client, Duration::from_secs(10))
+        .await
+        .expect("the client did not give up");
+    match event {
+        RaknetEvent::Disconnected(_, guid, reason) => {
+            assert_eq!(guid, old_guid);
+            assert_eq!(reason, DisconnectReason::ServerChanged);
+        }
+        _ => panic!("expected Disconnected"),
+    }
+}
+
+#[tokio::test]
+async fn peer_test() {
+    let network = Network::new(Conditions::default(), 19);
+    let first = simulated_server(&network).await;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut second = Server::with_transport(transport, "MCPE;raknet rs;".to_owned()).unwrap();
+    second.listen().await.unwrap();
+
+    //the second one dials out and keeps accepting on the same socket
+    let guid = second.connect(SERVER.parse().unwrap()).await.unwrap();
+    assert_eq!(guid, first.id);
+    let incoming = first.accept().await.unwrap();
+    assert_eq!(incoming.guid(), second.id);
+
+    let transport = network.bind("10.0.0.3:50000").unwrap();
+    let mut client = Client::with_transport(transport, CLIENT.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    incoming.send(&[0xfe, 0x1]).await.unwrap();
+    client.send(&[0xfe, 0x2]).await.unwrap();
+
+    //both connections share one event stream
+    let mut connected = vec![];
+    let mut packets = vec![];
+    wait_event(&second, Duration::from_secs(5), |event| {
+        match event {
+            RaknetEvent::Connected(addr, _) => connected.push(addr),
+            RaknetEvent::Packet(packet) => packets.push((packet.address, packet.data)),
+            _ => {}
+        }
+        (packets.len() == 2).then_some(())
+    })
+    .await;
+    connected.sort();
+    packets.sort();
+    let first_addr: SocketAddr = SERVER.parse().unwrap();
+    let client_addr: SocketAddr = "10.0.0.3:50000".parse().unwrap();
+    assert_eq!(connected, [first_addr, client_addr]);
+    assert_eq!(
+        packets,
+        [
+            (first_addr, vec![0xfe, 0x1]),
+            (client_addr, vec![0xfe, 0x2])
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unconnected_test() {
+    let network = Network::new(Conditions::default(), 23);
+    let server = simulated_server(&network).await;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    let server_addr: SocketAddr = SERVER.parse().unwrap();
+    let client_addr: SocketAddr = CLIENT.parse().unwrap();
+    client
+        .send_unconnected(server_addr, b"status?")
+        .await
+        .unwrap();
+    //from an address without a connection as well
+    let stranger = network.bind("10.0.0.3:50000").unwrap();
+    stranger
+        .send_to(&out_of_band(b"hello"), server_addr)
+        .await
+        .unwrap();
+
+    let mut received = vec![];
+    wait_event(&server, Duration::from_secs(5), |event| {
+        if let RaknetEvent::Unconnected(addr, data) = event {
+            received.push((addr, data));
+        }
+        (received.len() == 2).then_some(())
+    })
+    .await;
+    received.sort();
+    assert_eq!(
+        received,
+        [
+            (client_addr, b"status?".to_vec()),
+            ("10.0.0.3:50000".parse().unwrap(), b"hello".to_vec())
+        ]
+    );
+
+    server.send_unconnected(client_addr, b"ok").await.unwrap();
+    let reply = wait_event(&client, Duration::from_secs(5), |event| match event {
+        RaknetEvent::Unconnected(addr, data) => Some((addr, data)),
+        _ => None,
+    });
+    let reply = reply.await.unwrap();
+    assert_eq!(reply, (server_addr, b"ok".to_vec()));
+
+    assert!(server
+        .send_unconnected(client_addr, &[0; raknet::packets::MAX_OUT_OF_BAND + 1])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn unconnected_next_to_accept_test() {
+    let network = Network::new(Conditions::default(), 37);
+    let server = simulated_server(&network).await;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    let server_addr: SocketAddr = SERVER.parse().unwrap();
+    let stranger = network.bind("10.0.0.3:50000").unwrap();
+    let payload = out_of_band(b"flood");
+    for _ in 0..100 {
+        stranger.send_to(&payload, server_addr).await.unwrap();
+    }
+    wait_until(Duration::from_secs(5), || {
+        server.dropped_unconnected() >= 36
+    })
+    .await;
+    assert_eq!(server.dropped_unconnected(), 36);
+    assert_eq!(server.recv_unconnected().await.unwrap().len(), 64);
+
+    //the peer is still there for `accept`
+    let peer = tokio::time::timeout(Duration::from_secs(5), server.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(peer.remote_addr(), CLIENT.parse().unwrap());
+}
+
+#[tokio::test]
+async fn unconnected_before_connect_test() {
+    let network = Network::new(Conditions::default(), 35);
+    let server = simulated_server(&network).await;
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    //nothing to listen to yet, offline messages are read anyway
+    client.listen().await;
+
+    let client_addr: SocketAddr = CLIENT.parse().unwrap();
+    server.send_unconnected(client_addr, b"ok").await.unwrap();
+    let reply = wait_event(&client, Duration::from_secs(5), |event| match event {
+        RaknetEvent::Unconnected(addr, data) => Some((addr, data)),
+        _ => None,
+    });
+    let reply = reply.await.unwrap();
+    assert_eq!(reply, (SERVER.parse().unwrap(), b"ok".to_vec()));
+
+    //a flood is bounded until the application catches up
+    let stranger = network.bind("10.0.0.3:50000").unwrap();
+    let payload = out_of_band(b"flood");
+    for _ in 0..100 {
+        stranger.send_to(&payload, client_addr).await.unwrap();
+    }
+    wait_until(Duration::from_secs(5), || {
+        client.dropped_unconnected() >= 36
+    })
+    .await;
+    assert_eq!(client.dropped_unconnected(), 36);
+    assert_eq!(client.recv().await.unwrap().len(), 64);
+
+    //and the connection still comes up afterwards
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+}
+
+//sends a raw ping and returns whether a pong came back
+async fn pong_for(endpoint: &Endpoint, open: bool) -> bool {
+    endpoint
+        .send_to(&unconnected_ping(open, 2), SERVER.parse().unwrap())
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1500];
+    let pong = tokio::time::timeout(Duration::from_millis(300), endpoint.recv_from(&mut buf));
+    matches!(pong.await, Ok(Ok((_, _))) if buf[0] == UnconnectedPong::ID)
+}
+
+#[tokio::test]
+async fn max_connections_at_once_test() {
+    let network = Network::new(Conditions::default(), 38);
+    let transport = network.bind(SERVER).unwrap();
+    let mut server = Server::with_transport(transport, "MCPE;raknet rs;".to_owned()).unwrap();
+    server.set_max_connections(2);
+    server.listen().await.unwrap();
+
+    //all three handshakes are under way before any of them completes
+    let client = |address| {
+        let transport = network.bind(address).unwrap();
+        Client::with_transport(transport, SERVER.parse().unwrap()).unwrap()
+    };
+    let mut first = client("10.0.0.3:50000");
+    let mut second = client("10.0.0.4:50000");
+    let mut third = client("10.0.0.5:50000");
+    let results = tokio::join!(first.connect(), second.connect(), third.connect());
+    let results = [results.0, results.1, results.2];
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 2);
+    assert!(results
+        .iter()
+        .any(|result| matches!(result, Err(RaknetError::NoFreeIncomingConnections(_)))));
+}
+
+#[tokio::test]
+async fn open_connections_test() {
+    let network = Network::new(Conditions::default(), 29);
+    let transport = network.bind(SERVER).unwrap();
+    let mut server = Server::with_transport(transport, "MCPE;raknet rs;".to_owned()).unwrap();
+    server.set_max_connections(1);
+    server.listen().await.unwrap();
+    let pinger = network.bind("10.0.0.9:50000").unwrap();
+    assert!(pong_for(&pinger, true).await);
+
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    //full, only plain pings are answered
+    assert!(!pong_for(&pinger, true).await);
+    assert!(pong_for(&pinger, false).await);
+    let transport = network.bind("10.0.0.3:50000").unwrap();
+    let mut refused = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    match refused.connect().await {
+        Err(RaknetError::NoFreeIncomingConnections(_)) => {}
+        _ => panic!("expected NoFreeIncomingConnections"),
+    }
+
+    server.advertise(CLIENT.parse().unwrap()).await.unwrap();
+    let advertisement = wait_event(&client, Duration::from_secs(5), |event| match event {
+        RaknetEvent::Advertisement(addr, guid, motd) => Some((addr, guid, motd)),
+        _ => None,
+    });
+    let advertisement = advertisement.await.unwrap();
+    assert_eq!(
+        advertisement,
+        (
+            SERVER.parse().unwrap(),
+            server.id,
+            "MCPE;raknet rs;".to_owned()
+        )
+    );
+}
+
+//the loopback broadcast only arrives on Linux, the simulated network has no broadcasts
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn lan_discovery_test() {
+    //both games share the discovery port like Bedrock LAN worlds on 19132
+    let lan_port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut servers = vec![];
+    for motd in ["MCPE;first;", "MCPE;second;"] {
+        let mut server = Server::new("127.0.0.1:0".parse().unwrap(), motd.to_owned());
+        server.set_lan_discovery(Some(lan_port));
+        server.listen().await.unwrap();
+        servers.push(server);
+    }
+
+    let pinger = Ping::new().await.unwrap();
+    let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 255, 255, 255)), lan_port);
+    let mut pongs = pinger
+        .discover(target, Duration::from_millis(500))
+        .await
+        .unwrap();
+    pongs.sort_by(|a, b| a.motd.cmp(&b.motd));
+    let found: Vec<(u64, &str)> = pongs
+        .iter()
+        .map(|pong| (pong.guid, pong.motd.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (servers[0].id, "MCPE;first;"),
+            (servers[1].id, "MCPE;second;")
+        ]
+    );
+    assert!(pongs
+        .iter()
+        .all(|pong| pong.rtt < Duration::from_millis(500)));
+}
+
+#[tokio::test]
+async fn ping_many_test() {
+    let network = Network::new(Conditions::default(), 31);
+    let mut servers = vec![];
+    for address in ["10.0.0.5:19132", "10.0.0.6:19132", "10.0.0.7:19132"] {
+        let transport = network.bind(address).unwrap();
+        let mut server = Server::with_transport(transport, address.to_owned()).unwrap();
+        server.listen().await.unwrap();
+        servers.push(server);
+    }
+    network.set_conditions(Conditions {
+        loss: 0.3,
+        latency: Duration::from_millis(20),
+        ..Conditions::default()
+    });
+
+    let mut pinger = Ping::with_transport(network.bind(CLIENT).unwrap());
+    pinger.set_config(PingConfig {
+        timeout: Duration::from_millis(200),
+        retries: 15,
+    });
+    let mut targets: Vec<SocketAddr> = servers.iter().map(|s| s.local_addr).collect();
+    //nobody answers there
+    targets.insert(1, "10.0.0.8:19132".parse().unwrap());
+
+    let start = Instant::now();
+    let results = pinger.ping_many(&targets).await;
+    //the silent target uses up all 16 attempts, the others are answered meanwhile
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(results.len(), 4);
+    for (target, result) in targets.iter().zip(results) {
+        match servers.iter().find(|server| server.local_addr == *target) {
+            Some(server) => {
+                let pong = result.unwrap();
+                assert_eq!(pong.address, *target);
+                assert_eq!(pong.guid, server.id);
+                assert_eq!(pong.motd, target.to_string());
+                assert!(pong.rtt >= Duration::from_millis(40));
+            }
+            None => assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut),
+        }
+    }
+}
+
+#[tokio::test]
+async fn pong_provider_test() {
+    let network = Network::new(Conditions::default(), 32);
+    let transport = network.bind(SERVER).unwrap();
+    let mut server =
+        Server::with_transport(transport, "MCPE;raknet rs;390;1.17.42;0;10;".to_owned()).unwrap();
+    server.set_pong_provider(|ping: &raknet::PingInfo| {
+        format!("{} {} {}", ping.source, ping.client_guid, ping.connections)
+    });
+    server.listen().await.unwrap();
+
+    let pinger = network.bind("10.0.0.9:50000").unwrap();
+    let mut buf = [0u8; 1500];
+    let ping = unconnected_ping(false, 2);
+    pinger
+        .send_to(&ping, SERVER.parse().unwrap())
+        .await
+        .unwrap();
+    let (size, _) = pinger.recv_from(&mut buf).await.unwrap();
+    //id, time, guid, magic and the length of the motd come first
+    assert_eq!(&buf[35..size], b"10.0.0.9:50000 2 0");
+
+    //a spoofed request that never finishes the handshake is no player
+    let spoofer = network.bind("10.0.0.5:50000").unwrap();
+    let request2 = OpenConnectionRequest2::new(SERVER.parse().unwrap(), 1492, 7);
+    spoofer
+        .send_to(&encode(request2), SERVER.parse().unwrap())
+        .await
+        .unwrap();
+    spoofer.recv_from(&mut buf).await.unwrap();
+    assert_eq!(buf[0], OpenConnectionReply2::ID);
+    pinger
+        .send_to(&ping, SERVER.parse().unwrap())
+        .await
+        .unwrap();
+    let (size, _) = pinger.recv_from(&mut buf).await.unwrap();
+    assert_eq!(&buf[35..size], b"10.0.0.9:50000 2 0");
+
+    let transport = network.bind(CLIENT).unwrap();
+    let mut client = Client::with_transport(transport, SERVER.parse().unwrap()).unwrap();
+    client.connect().await.unwrap();
+    client.listen().await;
+    wait_connected(&client).await;
+
+    //the player count follows the connections, the rest of the motd stays
+    server.set_pong_provider(raknet::bedrock_players);
+    let ping = Ping::with_transport(network.bind("10.0.0.3:50000").unwrap());
+    let motd = ping
+        .ping(server.local_addr)
+        .await
+        .unwrap()
+        .bedrock_motd()
+        .unwrap();
+    assert_eq!(motd.players, 1);
+    assert_eq!(motd.max_players, 10);
+    server
+        .set_motd("MCPE;raknet rs;390;1.17.42;0;20;".to_owned())
+        .await
+        .unwrap();
+    let pong = ping.ping(server.local_addr).await.unwrap();
+    assert_eq!(pong.motd, "MCPE;raknet rs;390;1.17.42;1;20;");
+
+    server.clear_pong_provider();
+    let pong = ping.ping(server.local_addr).await.unwrap();
+    assert_eq!(pong.motd, "MCPE;raknet rs;390;1.17.42;0;20;");
+}